@@ -1,17 +1,59 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use anyhow::{Result, Context};
+use tracing::{info, warn};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
 use tch::{Device, Tensor, nn};
 use crate::models::{ModelRegistry, ModelType};
 use crate::utils::tensor_utils::TensorConversion;
 use crate::config::AIConfig;
 
+/// libtorch's RNG is process-global, so reseeding and drawing must not
+/// interleave across concurrent requests.
+static SAMPLING_RNG: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Number of responses `InferenceEngine` keeps for repeated requests by default.
+pub const DEFAULT_INFERENCE_CACHE_ENTRIES: usize = 1024;
+
+/// Device to run models on; see `InferenceEngine::with_device` and
+/// `ModelLoader::with_device`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceSelection {
+    /// The first CUDA device if one is available, otherwise the CPU.
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(usize),
+}
+
+impl DeviceSelection {
+    pub(crate) fn resolve(self) -> Device {
+        match self {
+            DeviceSelection::Auto => Device::cuda_if_available(),
+            DeviceSelection::Cpu => Device::Cpu,
+            DeviceSelection::Cuda(index) if (index as i64) < tch::Cuda::device_count() => Device::Cuda(index),
+            DeviceSelection::Cuda(index) => {
+                warn!("CUDA device {} is not available, falling back to CPU", index);
+                Device::Cpu
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct InferenceEngine {
     model_registry: Arc<ModelRegistry>,
     config: Arc<AIConfig>,
     device: Device,
+    cache: Arc<InferenceCache>,
+    /// Forward passes run so far, shared between clones.
+    forward_passes: Arc<AtomicU64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,44 +63,302 @@ pub struct InferenceRequest {
     pub params: Option<InferenceParams>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct InferenceParams {
     pub temperature: Option<f32>,
     pub top_p: Option<f32>,
+    pub top_k: Option<i64>,
     pub max_tokens: Option<i64>,
+    /// Seeds sampling so identical requests produce identical outputs.
+    pub seed: Option<u64>,
+    /// Penalty above 1.0 discourages tokens that were already generated.
+    pub repetition_penalty: Option<f32>,
+    /// Token sequences that end generation when emitted; not included in the output.
+    pub stop_sequences: Option<Vec<Vec<i64>>>,
+    /// Return the log-probability of each generated token.
+    #[serde(default)]
+    pub return_logprobs: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+impl InferenceParams {
+    /// Whether decoding must proceed token by token rather than sampling the
+    /// whole output in one draw.
+    fn needs_stepwise_decoding(&self) -> bool {
+        self.repetition_penalty.is_some() || self.stop_sequences.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinishReason {
+    MaxTokens,
+    StopSequence,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InferenceResponse {
     pub output: Vec<f32>,
+    /// Seconds spent producing the response; for cache hits, the lookup time.
     pub latency: f64,
+    /// Whether the response was replayed from the cache instead of computed.
+    #[serde(default)]
+    pub cached: bool,
+    /// Why generation ended; `None` for non-generative models.
+    pub finish_reason: Option<FinishReason>,
+    /// Log-probability of each output token under the temperature-scaled
+    /// distribution, when requested.
+    pub logprobs: Option<Vec<f32>>,
+}
+
+/// `(model_id, input hash, serialized params)` of a cacheable request.
+type CacheKey = (String, String, String);
+
+/// Responses to deterministic requests, evicted oldest first once full.
+struct InferenceCache {
+    capacity: usize,
+    entries: std::sync::Mutex<(HashMap<CacheKey, InferenceResponse>, VecDeque<CacheKey>)>,
+    /// Bumped on every invalidation, so a response computed with a model that
+    /// was replaced meanwhile isn't cached.
+    epoch: AtomicU64,
+}
+
+impl InferenceCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, entries: std::sync::Mutex::new((HashMap::new(), VecDeque::new())), epoch: AtomicU64::new(0) }
+    }
+
+    fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Key for `request`, or `None` if its output may differ between runs:
+    /// transformer sampling is only repeatable when seeded.
+    fn key(&self, request: &InferenceRequest, model_type: &ModelType) -> Option<CacheKey> {
+        let seeded = request.params.as_ref().map_or(false, |params| params.seed.is_some());
+        if self.capacity == 0 || (matches!(model_type, ModelType::Transformer) && !seeded) {
+            return None;
+        }
+        let input: Vec<u8> = request.input.iter().flat_map(|value| value.to_le_bytes()).collect();
+        let params = serde_json::to_string(&request.params).ok()?;
+        Some((request.model_id.clone(), hex::encode(Sha256::digest(&input)), params))
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<InferenceResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.0.get(key).cloned()
+    }
+
+    /// Caches `response` unless the cache was invalidated since `epoch`.
+    fn insert(&self, key: CacheKey, response: &InferenceResponse, epoch: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if self.epoch() != epoch {
+            return;
+        }
+        let (responses, order) = &mut *entries;
+        if responses.insert(key.clone(), response.clone()).is_none() {
+            order.push_back(key);
+        }
+        while responses.len() > self.capacity {
+            match order.pop_front() {
+                Some(oldest) => responses.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    /// Drops the responses of `model_id`, or every response when `None`.
+    fn invalidate(&self, model_id: Option<&str>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        let (responses, order) = &mut *entries;
+        let stale = |key: &CacheKey| model_id.map_or(true, |model_id| key.0 == model_id);
+        responses.retain(|key, _| !stale(key));
+        order.retain(|key| !stale(key));
+    }
+}
+
+/// Tokens produced by a transformer along with how generation ended.
+struct Generation {
+    tokens: Tensor,
+    logprobs: Option<Tensor>,
+    finish_reason: FinishReason,
 }
 
 impl InferenceEngine {
     pub fn new(model_registry: Arc<ModelRegistry>, config: Arc<AIConfig>) -> Self {
-        let device = if cuda::is_available() { Device::Cuda(0) } else { Device::Cpu };
-        Self { model_registry, config, device }
+        let device = DeviceSelection::default().resolve();
+        info!("Inference engine using device {:?}", device);
+        Self {
+            model_registry,
+            config,
+            device,
+            cache: Arc::new(InferenceCache::new(DEFAULT_INFERENCE_CACHE_ENTRIES)),
+            forward_passes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Runs inference on `selection` instead of the first CUDA device.
+    pub fn with_device(mut self, selection: DeviceSelection) -> Self {
+        self.device = selection.resolve();
+        info!("Inference engine using device {:?}", self.device);
+        self
+    }
+
+    /// Keeps up to `entries` responses for repeated deterministic requests;
+    /// zero disables caching.
+    pub fn with_cache_capacity(mut self, entries: usize) -> Self {
+        self.cache = Arc::new(InferenceCache::new(entries));
+        self
     }
 
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Number of forward passes run; requests answered from the cache don't count.
+    pub fn forward_passes(&self) -> u64 {
+        self.forward_passes.load(Ordering::Relaxed)
+    }
+
+    /// Drops the cached responses of `model_id`, e.g. after a new version of it
+    /// was loaded.
+    pub fn invalidate_model(&self, model_id: &str) {
+        self.cache.invalidate(Some(model_id));
+    }
+
+    /// Invalidates a model's cached responses each time `reloads` reports it
+    /// was reloaded; see `ModelLoader::subscribe_reloads`. The task exits once
+    /// the loader is dropped.
+    pub fn invalidate_on_reload(&self, mut reloads: broadcast::Receiver<String>) -> JoinHandle<()> {
+        let cache = Arc::clone(&self.cache);
+        tokio::spawn(async move {
+            loop {
+                match reloads.recv().await {
+                    Ok(model_id) => cache.invalidate(Some(&model_id)),
+                    // Some reloads were missed, so any model may be stale
+                    Err(RecvError::Lagged(_)) => cache.invalidate(None),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(model_id = %request.model_id))]
     pub async fn run_inference(&self, request: InferenceRequest) -> Result<InferenceResponse> {
         let model = self.model_registry.get_model(&request.model_id)
             .context("Failed to get model from registry")?;
 
-        let input_tensor = Tensor::of_slice(&request.input).to(self.device);
-        
         let start_time = std::time::Instant::now();
+
+        let cache_epoch = self.cache.epoch();
+        let cache_key = self.cache.key(&request, &model.model_type());
+        if let Some(response) = cache_key.as_ref().and_then(|key| self.cache.get(key)) {
+            let latency = start_time.elapsed().as_secs_f64();
+            return Ok(InferenceResponse { latency, cached: true, ..response });
+        }
+
+        let input_tensor = Tensor::of_slice(&request.input).to(self.device);
         
-        let output_tensor = match model.model_type() {
-            ModelType::Transformer => self.run_transformer_inference(model, input_tensor, request.params).await?,
-            ModelType::CNN => self.run_cnn_inference(model, input_tensor).await?,
+        let (output_tensor, logprobs, finish_reason) = match model.model_type() {
+            ModelType::Transformer => {
+                let generation = self.run_transformer_inference(model, input_tensor, request.params).await?;
+                (generation.tokens, generation.logprobs, Some(generation.finish_reason))
+            },
+            ModelType::CNN => (self.run_cnn_inference(model, input_tensor).await?, None, None),
             // Add more model types as needed
         };
 
         let latency = start_time.elapsed().as_secs_f64();
 
         let output = output_tensor.to_vec1::<f32>()?;
+        let logprobs = logprobs.map(|logprobs| logprobs.to_vec1::<f32>()).transpose()?;
+
+        let response = InferenceResponse { output, latency, cached: false, finish_reason, logprobs };
+        if let Some(key) = cache_key {
+            self.cache.insert(key, &response, cache_epoch);
+        }
+        Ok(response)
+    }
+
+    /// Runs several requests, batching those that share a model, input length
+    /// and params into a single forward pass. Requests that need token-by-token
+    /// decoding always run on their own, as do seeded ones: a batch draws all
+    /// rows from one seed, so each would get a different output than it gets
+    /// alone. Responses are returned in the order of `requests`.
+    pub async fn run_batch(&self, requests: Vec<InferenceRequest>) -> Result<Vec<InferenceResponse>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (index, request) in requests.iter().enumerate() {
+            let unbatchable = request.params.as_ref()
+                .map_or(false, |params| params.needs_stepwise_decoding() || params.seed.is_some());
+            if unbatchable {
+                groups.push(vec![index]);
+                continue;
+            }
+
+            let compatible = groups.iter_mut().find(|group| {
+                let first = &requests[group[0]];
+                first.model_id == request.model_id
+                    && first.input.len() == request.input.len()
+                    && first.params == request.params
+            });
+            match compatible {
+                Some(group) => group.push(index),
+                None => groups.push(vec![index]),
+            }
+        }
+
+        let mut requests: Vec<Option<InferenceRequest>> = requests.into_iter().map(Some).collect();
+        let mut responses: Vec<Option<InferenceResponse>> = (0..requests.len()).map(|_| None).collect();
 
-        Ok(InferenceResponse { output, latency })
+        for group in groups {
+            let batch: Vec<InferenceRequest> = group.iter()
+                .map(|&index| requests[index].take().expect("request assigned to one group"))
+                .collect();
+
+            let outputs = if batch.len() == 1 {
+                vec![self.run_inference(batch.into_iter().next().unwrap()).await?]
+            } else {
+                self.run_batched_forward(batch).await?
+            };
+
+            for (index, response) in group.into_iter().zip(outputs) {
+                responses[index] = Some(response);
+            }
+        }
+
+        Ok(responses.into_iter().map(|response| response.expect("every request answered")).collect())
+    }
+
+    /// Stacks same-shaped inputs along a new batch dimension, runs one forward
+    /// pass and splits the output back into per-request responses.
+    async fn run_batched_forward(&self, batch: Vec<InferenceRequest>) -> Result<Vec<InferenceResponse>> {
+        let model = self.model_registry.get_model(&batch[0].model_id)
+            .context("Failed to get model from registry")?;
+        let params = batch[0].params.clone();
+
+        let inputs: Vec<Tensor> = batch.iter().map(|request| Tensor::of_slice(&request.input)).collect();
+        let input_tensor = Tensor::stack(&inputs, 0).to(self.device);
+
+        let start_time = std::time::Instant::now();
+
+        let (output_tensor, logprobs, finish_reason) = match model.model_type() {
+            ModelType::Transformer => {
+                let generation = self.run_transformer_inference(model, input_tensor, params).await?;
+                (generation.tokens, generation.logprobs, Some(generation.finish_reason))
+            },
+            ModelType::CNN => (self.run_cnn_inference(model, input_tensor).await?, None, None),
+        };
+
+        let latency = start_time.elapsed().as_secs_f64();
+
+        (0..batch.len() as i64)
+            .map(|row| {
+                let output = output_tensor.get(row).to_vec1::<f32>()?;
+                let logprobs = logprobs.as_ref()
+                    .map(|logprobs| logprobs.get(row).to_vec1::<f32>())
+                    .transpose()?;
+                Ok(InferenceResponse { output, latency, cached: false, finish_reason, logprobs })
+            })
+            .collect()
     }
 
     async fn run_transformer_inference(
@@ -66,41 +366,132 @@ impl InferenceEngine {
         model: Arc<dyn nn::Module>,
         input: Tensor,
         params: Option<InferenceParams>
-    ) -> Result<Tensor> {
+    ) -> Result<Generation> {
         let params = params.unwrap_or_default();
         let temperature = params.temperature.unwrap_or(self.config.default_temperature);
         let top_p = params.top_p.unwrap_or(self.config.default_top_p);
         let max_tokens = params.max_tokens.unwrap_or(self.config.default_max_tokens);
 
         // Assuming the model is wrapped in no_grad for inference
+        self.forward_passes.fetch_add(1, Ordering::Relaxed);
         let output = tch::no_grad(|| {
             model.forward_t(&input, false)
                 .context("Failed to run transformer inference")
         })?;
 
-        // Apply temperature scaling and top-p sampling
+        // Apply temperature scaling, then top-k and top-p (nucleus) filtering
         let scaled_output = output / temperature;
-        let sampled_output = self.top_p_sampling(scaled_output, top_p, max_tokens)?;
+        if params.needs_stepwise_decoding() {
+            return self.decode_stepwise(scaled_output, &params, top_p, max_tokens);
+        }
 
-        Ok(sampled_output)
+        let log_probs = params.return_logprobs.then(|| scaled_output.log_softmax(-1, tch::Kind::Float));
+        let filtered_output = match params.top_k {
+            Some(k) => self.top_k_sampling(scaled_output, k),
+            None => scaled_output,
+        };
+        let sampled_output = self.top_p_sampling(filtered_output, top_p, max_tokens, params.seed)?;
+
+        Ok(Generation {
+            logprobs: log_probs.map(|log_probs| log_probs.gather(-1, &sampled_output, false)),
+            tokens: sampled_output,
+            finish_reason: FinishReason::MaxTokens,
+        })
+    }
+
+    /// Samples one token at a time so the repetition penalty can see what has
+    /// been generated so far and stop sequences can end generation early.
+    fn decode_stepwise(
+        &self,
+        logits: Tensor,
+        params: &InferenceParams,
+        top_p: f32,
+        max_tokens: i64,
+    ) -> Result<Generation> {
+        let penalty = params.repetition_penalty.unwrap_or(1.0);
+        let stop_sequences = params.stop_sequences.as_deref().unwrap_or(&[]);
+        let mut generated: Vec<i64> = Vec::new();
+        let mut logprobs: Vec<f32> = Vec::new();
+        let mut finish_reason = FinishReason::MaxTokens;
+
+        for step in 0..max_tokens.max(0) as u64 {
+            let mut step_logits = logits.copy();
+            if penalty != 1.0 && !generated.is_empty() {
+                let seen = Tensor::of_slice(&generated).to(logits.device());
+                let seen_logits = step_logits.index_select(-1, &seen);
+                // Shrink positive logits and push negative ones further down
+                let penalized = (&seen_logits * penalty as f64)
+                    .where_self(&seen_logits.lt(0.0), &(&seen_logits / penalty as f64));
+                step_logits = step_logits.scatter(-1, &seen, &penalized);
+            }
+
+            let log_probs = params.return_logprobs.then(|| step_logits.log_softmax(-1, tch::Kind::Float));
+            if let Some(k) = params.top_k {
+                step_logits = self.top_k_sampling(step_logits, k);
+            }
+            let step_seed = params.seed.map(|seed| seed.wrapping_add(step));
+            let token = self.top_p_sampling(step_logits, top_p, 1, step_seed)?.int64_value(&[0]);
+            generated.push(token);
+            if let Some(log_probs) = log_probs {
+                logprobs.push(log_probs.double_value(&[token]) as f32);
+            }
+
+            if let Some(stop) = stop_sequences.iter().find(|stop| !stop.is_empty() && generated.ends_with(stop)) {
+                generated.truncate(generated.len() - stop.len());
+                logprobs.truncate(generated.len());
+                finish_reason = FinishReason::StopSequence;
+                break;
+            }
+        }
+
+        Ok(Generation {
+            tokens: Tensor::of_slice(&generated),
+            logprobs: params.return_logprobs.then(|| Tensor::of_slice(&logprobs)),
+            finish_reason,
+        })
     }
 
     async fn run_cnn_inference(&self, model: Arc<dyn nn::Module>, input: Tensor) -> Result<Tensor> {
+        self.forward_passes.fetch_add(1, Ordering::Relaxed);
         tch::no_grad(|| {
             model.forward_t(&input, false)
                 .context("Failed to run CNN inference")
         })
     }
 
-    fn top_p_sampling(&self, logits: Tensor, p: f32, max_tokens: i64) -> Result<Tensor> {
+    /// Masks out every logit below the `k` highest so they can't be sampled.
+    fn top_k_sampling(&self, logits: Tensor, k: i64) -> Tensor {
+        let vocab_size = logits.size().last().copied().unwrap_or(1);
+        let k = k.clamp(1, vocab_size);
+        let (top_logits, _) = logits.topk(k, -1, true, false);
+        let (threshold, _) = top_logits.min_dim(-1, true);
+        logits.masked_fill(&logits.lt_tensor(&threshold), f64::NEG_INFINITY)
+    }
+
+    fn top_p_sampling(&self, logits: Tensor, p: f32, max_tokens: i64, seed: Option<u64>) -> Result<Tensor> {
         // Implement top-p (nucleus) sampling
-        let sorted_logits = logits.argsort(-1, true);
-        let cumulative_probs = sorted_logits.softmax(-1, tch::Kind::Float).cumsum(-1, tch::Kind::Float);
-        let sorted_indices_to_remove = cumulative_probs > p;
-        let indices_to_remove = sorted_indices_to_remove.scatter(1, sorted_logits, sorted_indices_to_remove);
+        let (sorted_logits, sorted_indices) = logits.sort(-1, true);
+        let sorted_probs = sorted_logits.softmax(-1, tch::Kind::Float);
+        let cumulative_probs = sorted_probs.cumsum(-1, tch::Kind::Float);
+        // Remove a token once the mass before it already exceeds p, so the most
+        // likely token is always kept
+        let sorted_indices_to_remove = (cumulative_probs - &sorted_probs).gt(p as f64);
+        let indices_to_remove = sorted_indices_to_remove.scatter(-1, &sorted_indices, &sorted_indices_to_remove);
         
         let filtered_logits = logits.masked_fill(&indices_to_remove, f64::NEG_INFINITY);
-        let sampled_tokens = filtered_logits.multinomial(max_tokens, true);
+        let probs = filtered_logits.softmax(-1, tch::Kind::Float);
+
+        let _rng = SAMPLING_RNG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let sampled_tokens = match seed {
+            Some(seed) => {
+                tch::manual_seed(seed as i64);
+                let sampled = probs.multinomial(max_tokens, true);
+                // Don't leave the global generator in a predictable state for unseeded requests
+                tch::manual_seed(rand::random::<i64>());
+                sampled
+            }
+            None => probs.multinomial(max_tokens, true),
+        };
 
         Ok(sampled_tokens)
     }
@@ -131,4 +522,254 @@ mod tests {
         assert_eq!(response.output.len(), 3);
         assert!(response.latency > 0.0);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_run_batch_preserves_request_order() {
+        let config = Arc::new(AIConfig::default());
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+
+        let engine = InferenceEngine::new(model_registry, config);
+
+        let inputs = vec![
+            vec![1.0, 2.0, 3.0],
+            vec![4.0, 5.0, 6.0],
+            vec![7.0, 8.0, 9.0],
+        ];
+        // Greedy, so the outputs don't depend on sampling; unseeded, so the
+        // requests are batched
+        let request = |input: &Vec<f32>| InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: input.clone(),
+            params: Some(InferenceParams { top_k: Some(1), ..Default::default() }),
+        };
+
+        let responses = engine.run_batch(inputs.iter().map(request).collect()).await.unwrap();
+        assert_eq!(responses.len(), 3);
+
+        for (input, response) in inputs.iter().zip(responses) {
+            let expected = engine.run_inference(request(input)).await.unwrap();
+            assert_eq!(response.output, expected.output);
+        }
+    }
+
+    #[test]
+    fn test_top_k_one_is_greedy() {
+        let engine = InferenceEngine::new(Arc::new(ModelRegistry::new()), Arc::new(AIConfig::default()));
+        let logits = Tensor::of_slice(&[0.1f32, 2.0, 0.5, 1.9]);
+
+        for _ in 0..5 {
+            let filtered = engine.top_k_sampling(logits.copy(), 1);
+            let sampled = engine.top_p_sampling(filtered, 0.9, 4, None).unwrap();
+            assert_eq!(Vec::<i64>::from(&sampled), vec![1, 1, 1, 1]);
+        }
+    }
+
+    #[test]
+    fn test_seeded_sampling_is_reproducible() {
+        let engine = InferenceEngine::new(Arc::new(ModelRegistry::new()), Arc::new(AIConfig::default()));
+        let logits = Tensor::of_slice(&[1.0f32; 32]);
+
+        let first = engine.top_p_sampling(logits.copy(), 1.0, 16, Some(42)).unwrap();
+        let second = engine.top_p_sampling(logits.copy(), 1.0, 16, Some(42)).unwrap();
+        assert_eq!(Vec::<i64>::from(&first), Vec::<i64>::from(&second));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_request_outputs_identical() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default()));
+
+        let request = || InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![1.0, 2.0, 3.0],
+            params: Some(InferenceParams {
+                seed: Some(7),
+                ..Default::default()
+            }),
+        };
+
+        let first = engine.run_inference(request()).await.unwrap();
+        let second = engine.run_inference(request()).await.unwrap();
+        let as_bytes = |output: &[f32]| output.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        assert_eq!(as_bytes(&first.output), as_bytes(&second.output));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_requests_identical_when_batched() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        // Without the cache, every response below is sampled
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default())).with_cache_capacity(0);
+
+        let request = || InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![1.0, 2.0, 3.0],
+            params: Some(InferenceParams { seed: Some(7), max_tokens: Some(4), ..Default::default() }),
+        };
+
+        let alone = engine.run_inference(request()).await.unwrap();
+        let batched = engine.run_batch(vec![request(), request(), request()]).await.unwrap();
+        let as_bytes = |output: &[f32]| output.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        for response in &batched {
+            assert_eq!(as_bytes(&response.output), as_bytes(&alone.output));
+        }
+    }
+
+    #[test]
+    fn test_stepwise_decoding_termination_reasons() {
+        let engine = InferenceEngine::new(Arc::new(ModelRegistry::new()), Arc::new(AIConfig::default()));
+        // Greedy decoding picks token 1, then token 2 once 1 is penalized. With both
+        // penalized, 1 (3.0 / 2) beats 2 (2.9 / 2) again and keeps winning
+        let logits = Tensor::of_slice(&[0.0f32, 3.0, 2.9, 0.0]);
+        let greedy = |stop_sequences| InferenceParams {
+            top_k: Some(1),
+            repetition_penalty: Some(2.0),
+            stop_sequences,
+            ..Default::default()
+        };
+
+        let generation = engine.decode_stepwise(logits.copy(), &greedy(None), 1.0, 5).unwrap();
+        assert_eq!(generation.finish_reason, FinishReason::MaxTokens);
+        assert_eq!(Vec::<i64>::from(&generation.tokens), vec![1, 2, 1, 1, 1]);
+
+        let generation = engine.decode_stepwise(logits.copy(), &greedy(Some(vec![vec![2, 1]])), 1.0, 5).unwrap();
+        assert_eq!(generation.finish_reason, FinishReason::StopSequence);
+        assert_eq!(Vec::<i64>::from(&generation.tokens), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_model_reports_finish_reason() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default()));
+
+        let response = engine.run_inference(InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![1.0, 2.0, 3.0],
+            params: Some(InferenceParams {
+                max_tokens: Some(4),
+                stop_sequences: Some(vec![]),
+                ..Default::default()
+            }),
+        }).await.unwrap();
+
+        assert_eq!(response.finish_reason, Some(FinishReason::MaxTokens));
+        assert_eq!(response.output.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_logprobs_returned_per_token() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default()));
+
+        for top_k in [None, Some(1)] {
+            let response = engine.run_inference(InferenceRequest {
+                model_id: "test_model".to_string(),
+                input: vec![1.0, 2.0, 3.0],
+                params: Some(InferenceParams {
+                    top_k,
+                    max_tokens: Some(4),
+                    return_logprobs: true,
+                    ..Default::default()
+                }),
+            }).await.unwrap();
+
+            let logprobs = response.logprobs.expect("logprobs requested");
+            assert_eq!(logprobs.len(), response.output.len());
+            assert!(logprobs.iter().all(|&logprob| logprob <= 0.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_deterministic_request_is_cached() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default()));
+
+        let request = |seed| InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![1.0, 2.0, 3.0],
+            params: Some(InferenceParams { seed, max_tokens: Some(4), ..Default::default() }),
+        };
+
+        let first = engine.run_inference(request(Some(7))).await.unwrap();
+        let repeat = engine.run_inference(request(Some(7))).await.unwrap();
+        assert!(!first.cached);
+        assert!(repeat.cached);
+        assert_eq!(engine.forward_passes(), 1);
+        assert_eq!(repeat.output, first.output);
+        assert_eq!(repeat.finish_reason, first.finish_reason);
+
+        // Different params are a different request
+        assert!(!engine.run_inference(request(Some(8))).await.unwrap().cached);
+
+        // Unseeded sampling is never replayed
+        engine.run_inference(request(None)).await.unwrap();
+        assert!(!engine.run_inference(request(None)).await.unwrap().cached);
+    }
+
+    #[tokio::test]
+    async fn test_inference_cache_evicts_oldest() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default())).with_cache_capacity(1);
+
+        let request = |input: f32| InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![input],
+            params: Some(InferenceParams { seed: Some(1), ..Default::default() }),
+        };
+
+        engine.run_inference(request(1.0)).await.unwrap();
+        engine.run_inference(request(2.0)).await.unwrap();
+        assert!(engine.run_inference(request(2.0)).await.unwrap().cached);
+        assert!(!engine.run_inference(request(1.0)).await.unwrap().cached);
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidated_when_model_reloaded() {
+        let model_registry = Arc::new(ModelRegistry::new());
+        model_registry.register("test_model".to_string(), Arc::new(MockModel::new())).unwrap();
+        let engine = InferenceEngine::new(model_registry, Arc::new(AIConfig::default()));
+        let (reloads, reload_rx) = broadcast::channel(8);
+        let invalidation = engine.invalidate_on_reload(reload_rx);
+
+        let request = || InferenceRequest {
+            model_id: "test_model".to_string(),
+            input: vec![1.0, 2.0, 3.0],
+            params: Some(InferenceParams { seed: Some(3), ..Default::default() }),
+        };
+
+        engine.run_inference(request()).await.unwrap();
+        assert!(engine.run_inference(request()).await.unwrap().cached);
+        assert_eq!(engine.forward_passes(), 1);
+
+        reloads.send("other_model".to_string()).unwrap();
+        reloads.send("test_model".to_string()).unwrap();
+        // Closing the channel lets the task finish once both are handled
+        drop(reloads);
+        invalidation.await.unwrap();
+
+        assert!(!engine.run_inference(request()).await.unwrap().cached);
+        assert_eq!(engine.forward_passes(), 2);
+        assert!(engine.run_inference(request()).await.unwrap().cached);
+    }
+
+    #[test]
+    fn test_configured_cpu_selects_device() {
+        let engine = InferenceEngine::new(Arc::new(ModelRegistry::new()), Arc::new(AIConfig::default()))
+            .with_device(DeviceSelection::Cpu);
+        assert_eq!(engine.device(), Device::Cpu);
+    }
+
+    #[test]
+    #[ignore = "needs at least two CUDA devices"]
+    fn test_configured_cuda_index_selects_device() {
+        let engine = InferenceEngine::new(Arc::new(ModelRegistry::new()), Arc::new(AIConfig::default()))
+            .with_device(DeviceSelection::Cuda(1));
+        assert_eq!(engine.device(), Device::Cuda(1));
+    }
+}
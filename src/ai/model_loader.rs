@@ -1,10 +1,20 @@
-use std::path::Path;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, Mutex as AsyncMutex, RwLock};
+use tokio::task::JoinHandle;
 use anyhow::{Result, Context};
+use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
-use tch::{CModule, Device};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tch::{CModule, Device, Kind, Tensor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tract_onnx::prelude::{Framework, InferenceModelExt, Tensor as OnnxTensor, TypedRunnableModel, TypedModel, tvec};
 
+use crate::ai::inference_engine::DeviceSelection;
 use crate::config::AIConfig;
 use crate::storage::ModelStorage;
 use crate::errors::ModelError;
@@ -16,51 +26,948 @@ pub struct ModelMetadata {
     pub task_type: String,
     pub input_shape: Vec<i64>,
     pub output_shape: Vec<i64>,
+    /// On-disk format; inferred from the model file's extension when absent.
+    #[serde(default)]
+    pub format: Option<ModelFormat>,
+    /// Hex-encoded SHA-256 of the model file, checked before the model is loaded.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Returned when a model file's contents don't match the checksum in its metadata.
+#[derive(Debug, thiserror::Error)]
+#[error("Integrity check failed for model {model_id}: expected sha256 {expected}, got {actual}")]
+pub struct IntegrityError {
+    pub model_id: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Computes the hex-encoded SHA-256 of a file without reading it into memory at once.
+pub(crate) async fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelFormat {
+    TorchScript,
+    Onnx,
+}
+
+impl ModelFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("onnx") => ModelFormat::Onnx,
+            _ => ModelFormat::TorchScript,
+        }
+    }
+}
+
+/// A loaded model that can run a forward pass regardless of its on-disk format.
+pub trait ModelBackend: Send + Sync {
+    fn format(&self) -> ModelFormat;
+    fn forward(&self, input: &Tensor) -> Result<Tensor>;
+}
+
+struct TorchScriptBackend {
+    module: CModule,
+}
+
+impl ModelBackend for TorchScriptBackend {
+    fn format(&self) -> ModelFormat {
+        ModelFormat::TorchScript
+    }
+
+    fn forward(&self, input: &Tensor) -> Result<Tensor> {
+        tch::no_grad(|| self.module.forward_ts(&[input]))
+            .context("Failed to run TorchScript forward pass")
+    }
+}
+
+struct OnnxBackend {
+    plan: TypedRunnableModel<TypedModel>,
+}
+
+impl OnnxBackend {
+    /// Loads the ONNX graph with its input fixed to the metadata's input shape,
+    /// and checks that the resulting output shape matches the metadata too.
+    fn load(model_path: &Path, metadata: &ModelMetadata) -> Result<Self> {
+        let input_shape = to_dims(&metadata.input_shape).context("Invalid input_shape in metadata")?;
+        let output_shape = to_dims(&metadata.output_shape).context("Invalid output_shape in metadata")?;
+
+        let model = tract_onnx::onnx()
+            .model_for_path(model_path)
+            .context("Failed to parse ONNX model")?
+            .with_input_fact(0, tract_onnx::prelude::f32::fact(&input_shape).into())
+            .context("Failed to set ONNX input shape")?
+            .into_optimized()
+            .with_context(|| format!("ONNX graph does not accept input shape {:?}", input_shape))?;
+
+        let graph_output = model.output_fact(0)?.shape.as_concrete().map(|dims| dims.to_vec());
+        if graph_output.as_deref() != Some(&output_shape[..]) {
+            return Err(anyhow::anyhow!(
+                "ONNX graph output shape {:?} does not match metadata {:?}",
+                graph_output, output_shape
+            ));
+        }
+
+        let plan = model.into_runnable().context("Failed to prepare ONNX model")?;
+        Ok(Self { plan })
+    }
+}
+
+impl ModelBackend for OnnxBackend {
+    fn format(&self) -> ModelFormat {
+        ModelFormat::Onnx
+    }
+
+    fn forward(&self, input: &Tensor) -> Result<Tensor> {
+        let shape: Vec<usize> = input.size().iter().map(|&d| d as usize).collect();
+        let values = Vec::<f32>::from(&input.to_kind(Kind::Float).to_device(Device::Cpu).flatten(0, -1));
+        let onnx_input = OnnxTensor::from_shape(&shape, &values)?;
+
+        let outputs = self.plan.run(tvec!(onnx_input.into()))
+            .context("Failed to run ONNX forward pass")?;
+        let output = outputs[0].to_array_view::<f32>()?;
+        let output_shape: Vec<i64> = output.shape().iter().map(|&d| d as i64).collect();
+        let output_values: Vec<f32> = output.iter().copied().collect();
+
+        Ok(Tensor::of_slice(&output_values).reshape(&output_shape).to_device(input.device()))
+    }
+}
+
+fn to_dims(shape: &[i64]) -> Result<Vec<usize>> {
+    shape.iter()
+        .map(|&d| usize::try_from(d).map_err(|_| anyhow::anyhow!("dimension {} is not a fixed size", d)))
+        .collect()
+}
+
+/// Rejects ids that would resolve outside a cache directory: empty ones, and
+/// ones with path separators, `..` or an absolute path.
+pub(crate) fn validate_model_id(model_id: &str) -> Result<()> {
+    if model_id.is_empty()
+        || model_id.contains(['/', '\\'])
+        || model_id.contains("..")
+        || Path::new(model_id).is_absolute()
+    {
+        return Err(anyhow::anyhow!("Invalid model id {:?}", model_id));
+    }
+    Ok(())
+}
+
+/// `<dir>/<model_id>.<extension>`. Unlike `Path::with_extension` this keeps any
+/// dots in the id, so `v1.2` and `v1.3` don't end up sharing `v1.<extension>`.
+fn cache_file(dir: &Path, model_id: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{}.{}", model_id, extension))
+}
+
+const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io";
+/// Extension given to cached models whose URI doesn't carry one.
+const DEFAULT_MODEL_EXTENSION: &str = "model";
+const MODEL_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Reload notifications a slow subscriber may fall behind by before it lags.
+const RELOAD_CHANNEL_CAPACITY: usize = 64;
+/// Models kept in memory unless `ModelLoader::with_max_loaded_models` says otherwise.
+pub const DEFAULT_MAX_LOADED_MODELS: usize = 8;
+
+/// Where to fetch a model and its metadata from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteModelSource {
+    /// `http(s)://` or `ipfs://` URI of the model file.
+    pub model_uri: String,
+    /// URI of the `.json` metadata; defaults to `model_uri` with a `.json` extension.
+    #[serde(default)]
+    pub metadata_uri: Option<String>,
+}
+
+/// `ModelStorage` that downloads models into a local cache directory the first
+/// time they're requested and serves the cached copy afterwards.
+pub struct RemoteModelStorage {
+    cache_dir: PathBuf,
+    sources: HashMap<String, RemoteModelSource>,
+    ipfs_gateway: String,
+    client: reqwest::Client,
+}
+
+impl RemoteModelStorage {
+    pub fn new(cache_dir: impl Into<PathBuf>, sources: HashMap<String, RemoteModelSource>) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            sources,
+            ipfs_gateway: DEFAULT_IPFS_GATEWAY.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_ipfs_gateway(mut self, gateway: impl Into<String>) -> Self {
+        self.ipfs_gateway = gateway.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    fn resolve_uri(&self, uri: &str) -> Result<String> {
+        if let Some(cid_path) = uri.strip_prefix("ipfs://") {
+            Ok(format!("{}/ipfs/{}", self.ipfs_gateway, cid_path))
+        } else if uri.starts_with("http://") || uri.starts_with("https://") {
+            Ok(uri.to_string())
+        } else {
+            Err(anyhow::anyhow!("Unsupported model URI scheme: {}", uri))
+        }
+    }
+
+    /// Where the model is cached: `<id>.<ext>`, keeping the extension of the
+    /// file the URI points at so the loader can tell its format.
+    fn cached_model_path(&self, model_id: &str, source: &RemoteModelSource) -> PathBuf {
+        let file_name = source.model_uri.rsplit('/').next().unwrap_or_default();
+        let extension = Path::new(file_name).extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or(DEFAULT_MODEL_EXTENSION);
+        cache_file(&self.cache_dir, model_id, extension)
+    }
+
+    /// Downloads `uri` to `dest`, resuming from a partial `.part` file left by an
+    /// earlier attempt when the server supports range requests.
+    async fn download(&self, uri: &str, dest: &Path) -> Result<()> {
+        let url = self.resolve_uri(uri)?;
+        let part_path = dest.with_extension(match dest.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{}.part", ext),
+            None => "part".to_string(),
+        });
+        let resume_from = tokio::fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+
+        let mut request = self.client.get(&url);
+        if resume_from > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+        }
+        let mut response = request.send().await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to download {}", url))?;
+
+        let mut file = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+            tracing::info!("Resuming download of {} from byte {}", url, resume_from);
+            tokio::fs::OpenOptions::new().append(true).open(&part_path).await?
+        } else {
+            tokio::fs::File::create(&part_path).await?
+        };
+        while let Some(chunk) = response.chunk().await
+            .with_context(|| format!("Download of {} interrupted", url))? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        tokio::fs::rename(&part_path, dest).await
+            .with_context(|| format!("Failed to move download into {}", dest.display()))
+    }
+}
+
+#[async_trait]
+impl ModelStorage for RemoteModelStorage {
+    async fn get_model_path(&self, model_id: &str) -> Result<PathBuf> {
+        validate_model_id(model_id)?;
+        let source = self.sources.get(model_id)
+            .ok_or_else(|| anyhow::anyhow!("No remote source configured for model {}", model_id))?;
+        let model_path = self.cached_model_path(model_id, source);
+        let metadata_path = cache_file(&self.cache_dir, model_id, "json");
+
+        if tokio::fs::try_exists(&model_path).await? && tokio::fs::try_exists(&metadata_path).await? {
+            return Ok(model_path);
+        }
+
+        tokio::fs::create_dir_all(&self.cache_dir).await
+            .context("Failed to create model cache directory")?;
+        let metadata_uri = source.metadata_uri.clone().unwrap_or_else(|| {
+            match source.model_uri.rsplit_once('.') {
+                Some((stem, ext)) if !ext.contains('/') => format!("{}.json", stem),
+                _ => format!("{}.json", source.model_uri),
+            }
+        });
+        self.download(&metadata_uri, &metadata_path).await?;
+        self.download(&source.model_uri, &model_path).await?;
+
+        verify_download(model_id, &model_path, &metadata_path).await?;
+
+        tracing::info!("Downloaded model {} into {}", model_id, model_path.display());
+        Ok(model_path)
+    }
+}
+
+/// Checks a freshly downloaded model against the checksum in its metadata,
+/// deleting the model file when they don't match.
+async fn verify_download(model_id: &str, model_path: &Path, metadata_path: &Path) -> Result<()> {
+    let metadata: ModelMetadata = serde_json::from_str(&tokio::fs::read_to_string(metadata_path).await?)
+        .context("Failed to parse downloaded metadata")?;
+    if let Some(expected) = &metadata.sha256 {
+        let actual = file_sha256(model_path).await?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            // Don't leave a corrupt file behind to be served as a cache hit
+            tokio::fs::remove_file(model_path).await.ok();
+            return Err(IntegrityError {
+                model_id: model_id.to_string(),
+                expected: expected.clone(),
+                actual,
+            }.into());
+        }
+    }
+    Ok(())
+}
+
+const DEFAULT_S3_REGION: &str = "us-east-1";
+const DEFAULT_S3_CACHE_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+/// SHA-256 of an empty body, which is what every GET carries.
+const EMPTY_PAYLOAD_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+fn default_s3_region() -> String {
+    DEFAULT_S3_REGION.to_string()
+}
+
+fn default_s3_model_extension() -> String {
+    "onnx".to_string()
+}
+
+fn default_s3_cache_bytes() -> u64 {
+    DEFAULT_S3_CACHE_BYTES
+}
+
+/// Access key used to sign requests to an S3-compatible service.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Set when using temporary credentials.
+    #[serde(default)]
+    pub session_token: Option<String>,
+}
+
+impl S3Credentials {
+    /// Reads `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+impl std::fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where `S3ModelStorage` finds models. Model `id` is stored under the object
+/// key `<prefix><id>.<model_extension>`, with its metadata at `<prefix><id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3StorageConfig {
+    /// Base URL of the service, e.g. `https://s3.eu-west-1.amazonaws.com` or
+    /// `http://minio:9000`. Buckets are addressed path-style.
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_s3_model_extension")]
+    pub model_extension: String,
+    /// Requests are sent unsigned when absent, which only works for public buckets.
+    #[serde(default)]
+    pub credentials: Option<S3Credentials>,
+    /// Total size the local cache may grow to before the least recently used
+    /// models are evicted.
+    #[serde(default = "default_s3_cache_bytes")]
+    pub max_cache_bytes: u64,
+}
+
+impl S3StorageConfig {
+    pub fn new(endpoint: impl Into<String>, bucket: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            bucket: bucket.into(),
+            region: default_s3_region(),
+            prefix: String::new(),
+            model_extension: default_s3_model_extension(),
+            credentials: S3Credentials::from_env(),
+            max_cache_bytes: DEFAULT_S3_CACHE_BYTES,
+        }
+    }
+}
+
+/// `ModelStorage` backed by an S3-compatible bucket. Models are downloaded into
+/// a local cache directory on first use, and the least recently used ones are
+/// evicted once the cache outgrows `max_cache_bytes`.
+pub struct S3ModelStorage {
+    config: S3StorageConfig,
+    cache_dir: PathBuf,
+    client: reqwest::Client,
+    /// When each cached model was last requested; models cached by an earlier
+    /// run fall back to their files' modification time.
+    last_used: Mutex<HashMap<String, SystemTime>>,
+    /// Held while a model is checked for or fetched into the cache, so each
+    /// model is downloaded once and never evicted while it's being fetched.
+    model_locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    /// Serializes evictions with each other.
+    eviction_lock: AsyncMutex<()>,
+}
+
+impl S3ModelStorage {
+    pub fn new(config: S3StorageConfig, cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config,
+            cache_dir: cache_dir.into(),
+            client: reqwest::Client::new(),
+            last_used: Mutex::new(HashMap::new()),
+            model_locks: Mutex::new(HashMap::new()),
+            eviction_lock: AsyncMutex::new(()),
+        }
+    }
+
+    fn model_lock(&self, model_id: &str) -> Arc<AsyncMutex<()>> {
+        Arc::clone(self.model_locks.lock().unwrap().entry(model_id.to_string()).or_default())
+    }
+
+    /// Removes an evicted model's lock, unless another caller already holds it
+    /// and would end up locking a different mutex than the next one.
+    fn forget_model_lock(&self, model_id: &str, lock: Arc<AsyncMutex<()>>) {
+        let mut locks = self.model_locks.lock().unwrap();
+        // Held only by the map and `lock`
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(model_id);
+        }
+    }
+
+    fn object_key(&self, model_id: &str, extension: &str) -> String {
+        format!("{}{}.{}", self.config.prefix, model_id, extension)
+    }
+
+    fn touch(&self, model_id: &str) {
+        self.last_used.lock().unwrap().insert(model_id.to_string(), SystemTime::now());
+    }
+
+    /// Downloads the object at `key` to `dest` through a `.part` file, so an
+    /// interrupted download is never mistaken for a cached one.
+    async fn get_object(&self, key: &str, dest: &Path) -> Result<()> {
+        let path = format!("/{}/{}", uri_encode(&self.config.bucket), key.split('/').map(uri_encode).collect::<Vec<_>>().join("/"));
+        let url = reqwest::Url::parse(&format!("{}{}", self.config.endpoint.trim_end_matches('/'), path))
+            .with_context(|| format!("Invalid S3 endpoint {}", self.config.endpoint))?;
+
+        let mut request = self.client.get(url.clone());
+        if let Some(credentials) = &self.config.credentials {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+                None => url.host_str().unwrap_or_default().to_string(),
+            };
+            let amz_date = amz_timestamp(SystemTime::now());
+            let mut headers = vec![
+                ("host", host),
+                ("x-amz-content-sha256", EMPTY_PAYLOAD_SHA256.to_string()),
+                ("x-amz-date", amz_date.clone()),
+            ];
+            if let Some(token) = &credentials.session_token {
+                headers.push(("x-amz-security-token", token.clone()));
+            }
+            let authorization = sigv4_authorization(
+                credentials, &self.config.region, "s3", "GET", url.path(), &headers, EMPTY_PAYLOAD_SHA256, &amz_date,
+            );
+            for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+                request = request.header(name, value);
+            }
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+
+        let mut response = request.send().await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to download s3://{}/{}", self.config.bucket, key))?;
+        let part_path = dest.with_extension(match dest.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{}.part", ext),
+            None => "part".to_string(),
+        });
+        let mut file = tokio::fs::File::create(&part_path).await?;
+        while let Some(chunk) = response.chunk().await
+            .with_context(|| format!("Download of s3://{}/{} interrupted", self.config.bucket, key))? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        tokio::fs::rename(&part_path, dest).await
+            .with_context(|| format!("Failed to move download into {}", dest.display()))
+    }
+
+    /// Deletes the least recently used models other than `keep` until the
+    /// cache fits in `max_cache_bytes`. Models another request is fetching
+    /// right now are skipped.
+    async fn evict(&self, keep: &str) -> Result<()> {
+        let _evicting = self.eviction_lock.lock().await;
+        // Size and last use of each cached model, keyed by model id
+        let mut cached: HashMap<String, (u64, SystemTime, Vec<PathBuf>)> = HashMap::new();
+        let mut entries = tokio::fs::read_dir(&self.cache_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+            if extension != self.config.model_extension && extension != "json" {
+                continue;
+            }
+            let model_id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(model_id) => model_id.to_string(),
+                None => continue,
+            };
+            let meta = entry.metadata().await?;
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let model = cached.entry(model_id).or_insert((0, SystemTime::UNIX_EPOCH, Vec::new()));
+            model.0 += meta.len();
+            model.1 = model.1.max(modified);
+            model.2.push(path);
+        }
+
+        let mut total: u64 = cached.values().map(|(size, _, _)| size).sum();
+        if total <= self.config.max_cache_bytes {
+            return Ok(());
+        }
+        let mut candidates: Vec<_> = {
+            let last_used = self.last_used.lock().unwrap();
+            cached.into_iter()
+                .filter(|(model_id, _)| model_id != keep)
+                .map(|(model_id, (size, modified, paths))| {
+                    let used = last_used.get(&model_id).copied().unwrap_or(modified);
+                    (used, model_id, size, paths)
+                })
+                .collect()
+        };
+        candidates.sort_by_key(|(used, ..)| *used);
+
+        for (_, model_id, size, paths) in candidates {
+            if total <= self.config.max_cache_bytes {
+                break;
+            }
+            let lock = self.model_lock(&model_id);
+            let guard = match lock.try_lock() {
+                Ok(guard) => guard,
+                Err(_) => continue,
+            };
+            for path in paths {
+                tokio::fs::remove_file(&path).await
+                    .with_context(|| format!("Failed to evict {}", path.display()))?;
+            }
+            drop(guard);
+            self.forget_model_lock(&model_id, lock);
+            self.last_used.lock().unwrap().remove(&model_id);
+            total -= size;
+            tracing::info!("Evicted model {} from the cache ({} bytes)", model_id, size);
+        }
+        if total > self.config.max_cache_bytes {
+            tracing::warn!("Model cache holds {} bytes, over its {} byte budget, because model {} alone exceeds it", total, self.config.max_cache_bytes, keep);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ModelStorage for S3ModelStorage {
+    async fn get_model_path(&self, model_id: &str) -> Result<PathBuf> {
+        validate_model_id(model_id)?;
+        let model_path = cache_file(&self.cache_dir, model_id, &self.config.model_extension);
+        let metadata_path = cache_file(&self.cache_dir, model_id, "json");
+
+        let lock = self.model_lock(model_id);
+        let guard = lock.lock().await;
+        if tokio::fs::try_exists(&model_path).await? && tokio::fs::try_exists(&metadata_path).await? {
+            self.touch(model_id);
+            return Ok(model_path);
+        }
+
+        tokio::fs::create_dir_all(&self.cache_dir).await
+            .context("Failed to create model cache directory")?;
+        self.get_object(&self.object_key(model_id, "json"), &metadata_path).await?;
+        self.get_object(&self.object_key(model_id, &self.config.model_extension), &model_path).await?;
+        verify_download(model_id, &model_path, &metadata_path).await?;
+        self.touch(model_id);
+
+        tracing::info!("Downloaded model {} from bucket {} into {}", model_id, self.config.bucket, model_path.display());
+        drop(guard);
+        self.evict(model_id).await?;
+        Ok(model_path)
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac_sha256(key: &[u8], message: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes a path segment the way AWS Signature Version 4 expects.
+fn uri_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Formats `time` as the `YYYYMMDD'T'HHMMSS'Z'` timestamp used in `x-amz-date`.
+fn amz_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year, month, day, secs_of_day / 3_600, secs_of_day % 3_600 / 60, secs_of_day % 60,
+    )
+}
+
+/// `Authorization` header value signing a request without query parameters
+/// with AWS Signature Version 4. `headers` are the signed headers, lowercase
+/// and sorted by name.
+#[allow(clippy::too_many_arguments)]
+fn sigv4_authorization(
+    credentials: &S3Credentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    canonical_uri: &str,
+    headers: &[(&str, String)],
+    payload_sha256: &str,
+    amz_date: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let canonical_headers: String = headers.iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method, canonical_uri, canonical_headers, signed_headers, payload_sha256,
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex::encode(Sha256::digest(canonical_request.as_bytes())),
+    );
+
+    let date_key = hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), date);
+    let region_key = hmac_sha256(&date_key, region);
+    let service_key = hmac_sha256(&region_key, service);
+    let signing_key = hmac_sha256(&service_key, "aws4_request");
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, hex::encode(hmac_sha256(&signing_key, &string_to_sign)),
+    )
+}
+
+/// Modification time and length of a model file and of its metadata file.
+type FileFingerprint = [(SystemTime, u64); 2];
+
+async fn file_fingerprint(model_path: &Path) -> Option<FileFingerprint> {
+    let stat = |path: PathBuf| async move {
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    };
+    Some([stat(model_path.to_path_buf()).await?, stat(model_path.with_extension("json")).await?])
+}
+
+struct LoadedModel {
+    model: Arc<dyn ModelBackend>,
+    metadata: ModelMetadata,
+    /// Value of the loader's access clock when this model was last used.
+    last_used: AtomicU64,
+    path: PathBuf,
+    /// State of the files on disk the last time the watcher looked at them.
+    fingerprint: Option<FileFingerprint>,
 }
 
 pub struct ModelLoader {
     config: AIConfig,
+    device: Device,
+    max_loaded_models: usize,
+    watch_models: bool,
+    warmup_on_load: bool,
     storage: Arc<dyn ModelStorage>,
-    loaded_models: Arc<RwLock<HashMap<String, (CModule, ModelMetadata)>>>,
+    loaded_models: Arc<RwLock<HashMap<String, LoadedModel>>>,
+    access_clock: AtomicU64,
+    /// Per-model locks held while a model is being fetched, so concurrent
+    /// callers for the same id wait for one load instead of starting their own.
+    loads_in_flight: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+    /// Ids of models the watcher swapped for a new version.
+    reloads: broadcast::Sender<String>,
 }
 
 impl ModelLoader {
     pub fn new(config: AIConfig, storage: Arc<dyn ModelStorage>) -> Self {
+        let device = if config.use_cuda { DeviceSelection::Cuda(0) } else { DeviceSelection::Cpu }.resolve();
         Self {
             config,
+            device,
+            max_loaded_models: DEFAULT_MAX_LOADED_MODELS,
+            watch_models: false,
+            warmup_on_load: false,
             storage,
             loaded_models: Arc::new(RwLock::new(HashMap::new())),
+            access_clock: AtomicU64::new(0),
+            loads_in_flight: Mutex::new(HashMap::new()),
+            reloads: broadcast::channel(RELOAD_CHANNEL_CAPACITY).0,
         }
     }
 
-    pub async fn load_model(&self, model_id: &str) -> Result<Arc<CModule>> {
+    /// Receives the id of each model the watcher reloads, so that anything
+    /// derived from the old version, such as cached responses, can be dropped.
+    pub fn subscribe_reloads(&self) -> broadcast::Receiver<String> {
+        self.reloads.subscribe()
+    }
+
+    pub async fn load_model(&self, model_id: &str) -> Result<Arc<dyn ModelBackend>> {
         // Check if model is already loaded
-        if let Some(model) = self.loaded_models.read().await.get(model_id) {
-            return Ok(Arc::new(model.0.clone()));
+        if let Some(model) = self.cached_model(model_id).await {
+            return Ok(model);
         }
 
+        let load_lock = Arc::clone(
+            self.loads_in_flight.lock()
+                .map_err(|_| anyhow::anyhow!("Failed to acquire lock on in-flight loads"))?
+                .entry(model_id.to_string())
+                .or_default()
+        );
+        let result = {
+            let _loading = load_lock.lock().await;
+            // Another caller may have finished loading while we waited
+            match self.cached_model(model_id).await {
+                Some(model) => Ok(model),
+                None => self.load_from_storage(model_id).await,
+            }
+        };
+
+        if let Ok(mut loads_in_flight) = self.loads_in_flight.lock() {
+            // Only the map and this call still hold the lock, so nobody is waiting on it
+            if Arc::strong_count(&load_lock) == 2 {
+                loads_in_flight.remove(model_id);
+            }
+        }
+
+        result
+    }
+
+    async fn cached_model(&self, model_id: &str) -> Option<Arc<dyn ModelBackend>> {
+        let loaded_models = self.loaded_models.read().await;
+        let loaded = loaded_models.get(model_id)?;
+        loaded.last_used.store(self.tick(), Ordering::Relaxed);
+        Some(Arc::clone(&loaded.model))
+    }
+
+    async fn load_from_storage(&self, model_id: &str) -> Result<Arc<dyn ModelBackend>> {
         // Load model from storage
         let model_path = self.storage.get_model_path(model_id).await
             .context("Failed to get model path")?;
-        let metadata = self.load_metadata(&model_path)
+        let fingerprint = file_fingerprint(&model_path).await;
+        let (model, metadata) = self.open_model(model_id, &model_path).await?;
+
+        // Store loaded model, making room for it first if we're at the limit
+        let mut loaded_models = self.loaded_models.write().await;
+        if !loaded_models.contains_key(model_id) {
+            while loaded_models.len() >= self.max_loaded_models {
+                Self::evict_least_recently_used(&mut loaded_models);
+            }
+        }
+        loaded_models.insert(
+            model_id.to_string(),
+            LoadedModel {
+                model: Arc::clone(&model),
+                metadata,
+                last_used: AtomicU64::new(self.tick()),
+                path: model_path,
+                fingerprint,
+            }
+        );
+        drop(loaded_models);
+
+        if self.warmup_on_load {
+            if let Err(e) = self.warmup(model_id).await {
+                tracing::warn!("Warmup of model {} failed, serving it anyway: {:#}", model_id, e);
+            }
+        }
+
+        Ok(model)
+    }
+
+    /// Runs a forward pass on a zero tensor of the model's `input_shape`, so lazy
+    /// initialization and kernel compilation don't land on the first real request.
+    pub async fn warmup(&self, model_id: &str) -> Result<()> {
+        let (model, input_shape) = match self.loaded_models.read().await.get(model_id) {
+            Some(loaded) => (Arc::clone(&loaded.model), loaded.metadata.input_shape.clone()),
+            None => return Err(ModelError::NotLoaded(model_id.to_string()).into()),
+        };
+
+        // Dynamic dimensions give no size to build a sample input with
+        if let Some(dim) = input_shape.iter().find(|&&dim| dim <= 0) {
+            return Err(anyhow::anyhow!("Can't warm up model {}: input dimension {} is not a fixed size", model_id, dim));
+        }
+        let start = Instant::now();
+        let input = Tensor::f_zeros(&input_shape, (Kind::Float, self.device()))
+            .context("Failed to build warmup input")?;
+        model.forward(&input).context("Warmup forward pass failed")?;
+        tracing::info!("Warmed up model {} in {:?}", model_id, start.elapsed());
+        Ok(())
+    }
+
+    /// Loads models onto `selection` instead of the device implied by `use_cuda`.
+    /// Should match the inference engine's device.
+    pub fn with_device(mut self, selection: DeviceSelection) -> Self {
+        self.device = selection.resolve();
+        self
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Keeps at most `n` models loaded, evicting the least recently used one to
+    /// make room for another.
+    pub fn with_max_loaded_models(mut self, n: usize) -> Self {
+        self.max_loaded_models = n.max(1);
+        self
+    }
+
+    /// Lets `spawn_watcher` poll loaded models' files and reload any that change.
+    pub fn with_model_watching(mut self, enabled: bool) -> Self {
+        self.watch_models = enabled;
+        self
+    }
+
+    /// Warms each model up right after it's loaded; see `warmup`.
+    pub fn with_warmup_on_load(mut self, enabled: bool) -> Self {
+        self.warmup_on_load = enabled;
+        self
+    }
+
+    /// Reads the metadata next to `model_path`, verifies the file's checksum and
+    /// builds the backend for its format.
+    async fn open_model(&self, model_id: &str, model_path: &Path) -> Result<(Arc<dyn ModelBackend>, ModelMetadata)> {
+        let metadata = self.load_metadata(model_path).await
             .context("Failed to load model metadata")?;
 
-        let device = if self.config.use_cuda {
-            Device::Cuda(0)
-        } else {
-            Device::Cpu
+        if let Some(expected) = &metadata.sha256 {
+            let actual = file_sha256(model_path).await?;
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(IntegrityError {
+                    model_id: model_id.to_string(),
+                    expected: expected.clone(),
+                    actual,
+                }.into());
+            }
+        }
+
+        let format = metadata.format.unwrap_or_else(|| ModelFormat::from_path(model_path));
+        let model: Arc<dyn ModelBackend> = match format {
+            ModelFormat::TorchScript => {
+                let module = CModule::load_on_device(model_path, self.device())
+                    .context("Failed to load model")?;
+                Arc::new(TorchScriptBackend { module })
+            },
+            ModelFormat::Onnx => Arc::new(OnnxBackend::load(model_path, &metadata)
+                .context("Failed to load ONNX model")?),
         };
 
-        let model = CModule::load_on_device(&model_path, device)
-            .context("Failed to load model")?;
+        Ok((model, metadata))
+    }
 
-        // Store loaded model
-        self.loaded_models.write().await.insert(
-            model_id.to_string(),
-            (model.clone(), metadata)
-        );
+    /// Starts polling the files of loaded models when watching is enabled,
+    /// reloading any that change. The watcher exits once the loader is dropped.
+    pub fn spawn_watcher(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if !self.watch_models {
+            return None;
+        }
+
+        let loader = Arc::downgrade(self);
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MODEL_WATCH_INTERVAL);
+            loop {
+                interval.tick().await;
+                match loader.upgrade() {
+                    Some(loader) => loader.reload_changed_models().await,
+                    None => break,
+                }
+            }
+        }))
+    }
 
-        Ok(Arc::new(model))
+    /// Reloads every loaded model whose file or metadata changed on disk. The old
+    /// version keeps serving until the new one is ready, and is kept if the reload fails.
+    async fn reload_changed_models(&self) {
+        let watched: Vec<(String, PathBuf, Option<FileFingerprint>)> = self.loaded_models.read().await
+            .iter()
+            .map(|(id, loaded)| (id.clone(), loaded.path.clone(), loaded.fingerprint))
+            .collect();
+
+        for (model_id, model_path, fingerprint) in watched {
+            let current = file_fingerprint(&model_path).await;
+            // A missing file is most likely mid-replacement; look again next tick
+            if current.is_none() || current == fingerprint {
+                continue;
+            }
+
+            let reloaded = self.open_model(&model_id, &model_path).await;
+            let mut loaded_models = self.loaded_models.write().await;
+            let loaded = match loaded_models.get_mut(&model_id) {
+                Some(loaded) => loaded,
+                // Unloaded while we were reading the new version
+                None => continue,
+            };
+            loaded.fingerprint = current;
+
+            match reloaded {
+                Ok((model, metadata)) => {
+                    loaded.model = model;
+                    loaded.metadata = metadata;
+                    // Sent before the lock is released, so subscribers hear of
+                    // the reload before anyone can use the new version
+                    self.reloads.send(model_id.clone()).ok();
+                    drop(loaded_models);
+                    match file_sha256(&model_path).await {
+                        Ok(checksum) => tracing::info!("Reloaded model {} (sha256 {})", model_id, checksum),
+                        Err(_) => tracing::info!("Reloaded model {}", model_id),
+                    }
+                },
+                Err(e) => tracing::warn!("Failed to reload model {}, keeping the previous version: {:#}", model_id, e),
+            }
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.access_clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn evict_least_recently_used(loaded_models: &mut HashMap<String, LoadedModel>) {
+        let oldest = loaded_models.iter()
+            .min_by_key(|(_, loaded)| loaded.last_used.load(Ordering::Relaxed))
+            .map(|(id, _)| id.clone());
+
+        if let Some(model_id) = oldest {
+            loaded_models.remove(&model_id);
+            tracing::info!("Unloaded model {} to stay within the loaded model limit", model_id);
+        }
     }
 
     async fn load_metadata(&self, model_path: &Path) -> Result<ModelMetadata> {
@@ -78,8 +985,8 @@ impl ModelLoader {
     }
 
     pub async fn get_model_metadata(&self, model_id: &str) -> Result<ModelMetadata> {
-        if let Some(model) = self.loaded_models.read().await.get(model_id) {
-            Ok(model.1.clone())
+        if let Some(loaded) = self.loaded_models.read().await.get(model_id) {
+            Ok(loaded.metadata.clone())
         } else {
             Err(ModelError::NotLoaded(model_id.to_string()).into())
         }
@@ -91,6 +998,8 @@ mod tests {
     use super::*;
     use mockall::predicate::*;
     use mockall::mock;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::AsyncBufReadExt;
 
     mock! {
         ModelStorage {}
@@ -115,4 +1024,496 @@ mod tests {
          let result = loader.load_model("test_model").await;
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_configured_device_used_for_loading() {
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(MockModelStorage::new()));
+        assert_eq!(loader.device(), Device::Cpu);
+
+        let loader = ModelLoader::new(AIConfig::default(), Arc::new(MockModelStorage::new()))
+            .with_device(DeviceSelection::Cpu);
+        assert_eq!(loader.device(), Device::Cpu);
+    }
+
+    /// Writes the ReLU ONNX fixture and its metadata under `dir` as `<id>.onnx`.
+    fn write_onnx_fixture(dir: &Path, id: &str) -> PathBuf {
+        let model_path = dir.join(format!("{}.onnx", id));
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/relu.onnx"),
+            &model_path,
+        ).unwrap();
+        let metadata = ModelMetadata {
+            id: id.to_string(),
+            version: "1".to_string(),
+            task_type: "test".to_string(),
+            input_shape: vec![1, 4],
+            output_shape: vec![1, 4],
+            format: None,
+            sha256: None,
+        };
+        std::fs::write(model_path.with_extension("json"), serde_json::to_string(&metadata).unwrap()).unwrap();
+        model_path
+    }
+
+    /// Rewrites the fixture's metadata so that it pins the given checksum.
+    fn pin_sha256(model_path: &Path, sha256: &str) {
+        let metadata_path = model_path.with_extension("json");
+        let mut metadata: ModelMetadata = serde_json::from_str(&std::fs::read_to_string(&metadata_path).unwrap()).unwrap();
+        metadata.sha256 = Some(sha256.to_string());
+        std::fs::write(metadata_path, serde_json::to_string(&metadata).unwrap()).unwrap();
+    }
+
+    fn storage_for(dir: &Path) -> MockModelStorage {
+        let dir = dir.to_path_buf();
+        let mut mock_storage = MockModelStorage::new();
+        mock_storage
+            .expect_get_model_path()
+            .returning(move |id| Ok(dir.join(format!("{}.onnx", id))));
+        mock_storage
+    }
+
+    #[tokio::test]
+    async fn test_load_onnx_model() {
+        let dir = tempfile::tempdir().unwrap();
+        write_onnx_fixture(dir.path(), "relu");
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(storage_for(dir.path())));
+        let model = loader.load_model("relu").await.expect("Failed to load ONNX model");
+        assert_eq!(model.format(), ModelFormat::Onnx);
+
+        let input = Tensor::of_slice(&[-1.0f32, 2.0, -3.0, 4.0]).reshape(&[1, 4]);
+        let output = model.forward(&input).unwrap();
+        assert_eq!(output.size(), vec![1, 4]);
+        assert_eq!(Vec::<f32>::from(&output.flatten(0, -1)), vec![0.0, 2.0, 0.0, 4.0]);
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_model_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        for id in ["a", "b", "c"] {
+            write_onnx_fixture(dir.path(), id);
+        }
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(storage_for(dir.path()))).with_max_loaded_models(2);
+
+        loader.load_model("a").await.unwrap();
+        loader.load_model("b").await.unwrap();
+        // Touch "a" so that "b" becomes the least recently used
+        loader.load_model("a").await.unwrap();
+        loader.load_model("c").await.unwrap();
+
+        assert!(loader.get_model_metadata("a").await.is_ok());
+        assert!(loader.get_model_metadata("c").await.is_ok());
+        let err = loader.get_model_metadata("b").await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<ModelError>(), Some(ModelError::NotLoaded(id)) if id == "b"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_loads_fetch_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let model_path = write_onnx_fixture(dir.path(), "shared");
+
+        let mut mock_storage = MockModelStorage::new();
+        mock_storage
+            .expect_get_model_path()
+            .with(eq("shared"))
+            .times(1)
+            .returning(move |_| Ok(model_path.clone()));
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(mock_storage));
+
+        let (first, second) = tokio::join!(loader.load_model("shared"), loader.load_model("shared"));
+        assert!(Arc::ptr_eq(&first.unwrap(), &second.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_checksum_verified_before_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let good_path = write_onnx_fixture(dir.path(), "good");
+        let bad_path = write_onnx_fixture(dir.path(), "bad");
+
+        let checksum = hex::encode(Sha256::digest(std::fs::read(&good_path).unwrap()));
+        pin_sha256(&good_path, &checksum);
+        pin_sha256(&bad_path, &"0".repeat(64));
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(storage_for(dir.path())));
+
+        assert!(loader.load_model("good").await.is_ok());
+
+        let err = loader.load_model("bad").await.err().expect("Mismatched checksum should be rejected");
+        let integrity = err.downcast_ref::<IntegrityError>().expect("Expected an IntegrityError");
+        assert_eq!(integrity.model_id, "bad");
+        assert_eq!(integrity.actual, checksum);
+        assert!(loader.get_model_metadata("bad").await.is_err());
+    }
+
+    /// Serves the files in `dir` over plain HTTP, counting the requests it receives.
+    async fn serve_dir(dir: PathBuf) -> (String, Arc<AtomicUsize>) {
+        serve_dir_with(dir, |_| true).await
+    }
+
+    /// Like `serve_dir`, but answers 403 to requests whose header lines
+    /// `authorize` rejects.
+    async fn serve_dir_with(dir: PathBuf, authorize: fn(&[String]) -> bool) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let dir = dir.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = tokio::io::BufReader::new(reader).lines();
+                    let request_line = lines.next_line().await.unwrap().unwrap_or_default();
+                    let mut headers = Vec::new();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line.is_empty() {
+                            break;
+                        }
+                        headers.push(line.to_lowercase());
+                    }
+                    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                    let response = match std::fs::read(dir.join(path.trim_start_matches('/'))) {
+                        _ if !authorize(&headers) => b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                        Ok(body) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(), body].concat(),
+                        Err(_) => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    writer.write_all(&response).await.unwrap();
+                });
+            }
+        });
+        (format!("http://{}", addr), requests)
+    }
+
+    #[tokio::test]
+    async fn test_remote_model_downloaded_once() {
+        let origin = tempfile::tempdir().unwrap();
+        let model_path = write_onnx_fixture(origin.path(), "relu");
+        let checksum = hex::encode(Sha256::digest(std::fs::read(&model_path).unwrap()));
+        pin_sha256(&model_path, &checksum);
+        let (base_url, requests) = serve_dir(origin.path().to_path_buf()).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let sources = HashMap::from([(
+            "relu".to_string(),
+            RemoteModelSource { model_uri: format!("{}/relu.onnx", base_url), metadata_uri: None },
+        )]);
+        let storage = RemoteModelStorage::new(cache.path(), sources);
+
+        // Cache miss: both the model and its metadata are fetched
+        let path = storage.get_model_path("relu").await.unwrap();
+        assert_eq!(path, cache.path().join("relu.onnx"));
+        assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(&model_path).unwrap());
+        assert!(path.with_extension("json").exists());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Cache hit: served locally without touching the network
+        assert_eq!(storage.get_model_path("relu").await.unwrap(), path);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(storage));
+        assert!(loader.load_model("relu").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_remote_dotted_model_ids_cached_apart() {
+        let origin = tempfile::tempdir().unwrap();
+        let (base_url, requests) = serve_dir(origin.path().to_path_buf()).await;
+        let mut sources = HashMap::new();
+        for id in ["v1.2", "v1.3"] {
+            write_onnx_fixture(origin.path(), id);
+            sources.insert(id.to_string(), RemoteModelSource { model_uri: format!("{}/{}.onnx", base_url, id), metadata_uri: None });
+        }
+        sources.insert("../escape".to_string(), RemoteModelSource { model_uri: format!("{}/v1.2.onnx", base_url), metadata_uri: None });
+
+        let cache = tempfile::tempdir().unwrap();
+        let storage = RemoteModelStorage::new(cache.path(), sources);
+
+        assert_eq!(storage.get_model_path("v1.2").await.unwrap(), cache.path().join("v1.2.onnx"));
+        assert_eq!(storage.get_model_path("v1.3").await.unwrap(), cache.path().join("v1.3.onnx"));
+        assert!(cache.path().join("v1.2.json").exists());
+        assert!(cache.path().join("v1.3.json").exists());
+        assert!(!cache.path().join("v1.onnx").exists());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // Ids that would leave the cache directory never reach the network
+        assert!(storage.get_model_path("../escape").await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_model_id_validation() {
+        for id in ["relu", "v1.2", "resnet-50_int8"] {
+            assert!(validate_model_id(id).is_ok(), "{} should be accepted", id);
+        }
+        for id in ["", "..", "../relu", "a/b", "a\\b", "/etc/passwd", "v1..2"] {
+            assert!(validate_model_id(id).is_err(), "{} should be rejected", id);
+        }
+    }
+
+    fn s3_config(endpoint: &str) -> S3StorageConfig {
+        S3StorageConfig {
+            prefix: "models/".to_string(),
+            credentials: Some(S3Credentials {
+                access_key_id: "test-access-key".to_string(),
+                secret_access_key: "test-secret-key".to_string(),
+                session_token: None,
+            }),
+            ..S3StorageConfig::new(endpoint, "weights")
+        }
+    }
+
+    /// Accepts only requests signed with `s3_config`'s access key.
+    fn is_signed(headers: &[String]) -> bool {
+        headers.iter().any(|h| h.starts_with("authorization: aws4-hmac-sha256 credential=test-access-key/"))
+            && headers.iter().any(|h| h.starts_with("x-amz-date: "))
+            && headers.iter().any(|h| h.starts_with("x-amz-content-sha256: "))
+    }
+
+    /// Lays out a bucket as the mock server sees it: `<root>/<bucket>/<key>`.
+    fn s3_bucket(root: &Path) -> PathBuf {
+        let objects = root.join("weights/models");
+        std::fs::create_dir_all(&objects).unwrap();
+        objects
+    }
+
+    #[tokio::test]
+    async fn test_s3_model_downloaded_once() {
+        let origin = tempfile::tempdir().unwrap();
+        let model_path = write_onnx_fixture(&s3_bucket(origin.path()), "relu");
+        let checksum = hex::encode(Sha256::digest(std::fs::read(&model_path).unwrap()));
+        pin_sha256(&model_path, &checksum);
+        let (endpoint, requests) = serve_dir_with(origin.path().to_path_buf(), is_signed).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let storage = S3ModelStorage::new(s3_config(&endpoint), cache.path());
+
+        let path = storage.get_model_path("relu").await.unwrap();
+        assert_eq!(path, cache.path().join("relu.onnx"));
+        assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(&model_path).unwrap());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // The cached copy is reused without contacting the bucket
+        assert_eq!(storage.get_model_path("relu").await.unwrap(), path);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(storage));
+        assert!(loader.load_model("relu").await.is_ok());
+
+        // Unsigned requests are refused by the bucket
+        let anonymous_cache = tempfile::tempdir().unwrap();
+        let config = S3StorageConfig { credentials: None, ..s3_config(&endpoint) };
+        let anonymous = S3ModelStorage::new(config, anonymous_cache.path());
+        assert!(anonymous.get_model_path("relu").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_s3_cache_evicts_least_recently_used() {
+        let origin = tempfile::tempdir().unwrap();
+        let bucket = s3_bucket(origin.path());
+        let model_size = |path: PathBuf| std::fs::metadata(&path).unwrap().len() + std::fs::metadata(path.with_extension("json")).unwrap().len();
+        let one_model = ["a", "b", "c"].iter()
+            .map(|id| model_size(write_onnx_fixture(&bucket, id)))
+            .max()
+            .unwrap();
+        let (endpoint, requests) = serve_dir_with(origin.path().to_path_buf(), is_signed).await;
+
+        // Room for two models, not three
+        let cache = tempfile::tempdir().unwrap();
+        let config = S3StorageConfig { max_cache_bytes: one_model * 2, ..s3_config(&endpoint) };
+        let storage = S3ModelStorage::new(config, cache.path());
+
+        storage.get_model_path("a").await.unwrap();
+        storage.get_model_path("b").await.unwrap();
+        // Using `a` again makes `b` the least recently used
+        storage.get_model_path("a").await.unwrap();
+        storage.get_model_path("c").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+
+        assert!(cache.path().join("a.onnx").exists());
+        assert!(!cache.path().join("b.onnx").exists());
+        assert!(!cache.path().join("b.json").exists());
+        assert!(cache.path().join("c.onnx").exists());
+        assert!(!storage.model_locks.lock().unwrap().contains_key("b"));
+
+        // An evicted model is fetched again on demand
+        storage.get_model_path("b").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 8);
+    }
+
+    #[tokio::test]
+    async fn test_s3_dotted_model_ids_cached_apart() {
+        let origin = tempfile::tempdir().unwrap();
+        let bucket = s3_bucket(origin.path());
+        for id in ["v1.2", "v1.3"] {
+            write_onnx_fixture(&bucket, id);
+        }
+        let (endpoint, requests) = serve_dir_with(origin.path().to_path_buf(), is_signed).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let storage = S3ModelStorage::new(s3_config(&endpoint), cache.path());
+
+        // Concurrent requests for one model share a single download
+        let (first, second) = tokio::join!(storage.get_model_path("v1.2"), storage.get_model_path("v1.2"));
+        assert_eq!(first.unwrap(), cache.path().join("v1.2.onnx"));
+        assert_eq!(second.unwrap(), cache.path().join("v1.2.onnx"));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        assert_eq!(storage.get_model_path("v1.3").await.unwrap(), cache.path().join("v1.3.onnx"));
+        assert!(cache.path().join("v1.2.json").exists());
+        assert!(cache.path().join("v1.3.json").exists());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        assert!(storage.get_model_path("../v1.2").await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_sigv4_signature() {
+        // `get-vanilla` from the AWS Signature Version 4 test suite
+        let credentials = S3Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let amz_date = amz_timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(1_440_938_160));
+        assert_eq!(amz_date, "20150830T123600Z");
+        let headers = [("host", "example.amazonaws.com".to_string()), ("x-amz-date", amz_date.clone())];
+        assert_eq!(
+            sigv4_authorization(&credentials, "us-east-1", "service", "GET", "/", &headers, EMPTY_PAYLOAD_SHA256, &amz_date),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(uri_encode("model v1+final"), "model%20v1%2Bfinal");
+    }
+
+    #[tokio::test]
+    async fn test_watched_model_reloaded_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let model_path = write_onnx_fixture(dir.path(), "watched");
+
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = Arc::new(ModelLoader::new(config, Arc::new(storage_for(dir.path()))).with_model_watching(true));
+        let watcher = loader.spawn_watcher().expect("watching is enabled");
+        let mut reloads = loader.subscribe_reloads();
+
+        let input = Tensor::of_slice(&[-1.0f32, 0.0, 1.0, 2.0]).reshape(&[1, 4]);
+        let model = loader.load_model("watched").await.unwrap();
+        assert_eq!(Vec::<f32>::from(&model.forward(&input).unwrap().flatten(0, -1)), vec![0.0, 0.0, 1.0, 2.0]);
+
+        // Swap the ReLU graph for a Tanh one in place
+        std::fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tanh.onnx"), &model_path).unwrap();
+
+        let deadline = tokio::time::Instant::now() + MODEL_WATCH_INTERVAL * 10;
+        loop {
+            let output = loader.load_model("watched").await.unwrap().forward(&input).unwrap();
+            if Vec::<f32>::from(&output.flatten(0, -1))[0] < 0.0 {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "Model was not reloaded after its file changed");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(reloads.try_recv().unwrap(), "watched");
+
+        watcher.abort();
+    }
+
+    /// Backend that is slow on its first forward pass, like a model with lazy initialization.
+    struct ColdStartBackend {
+        calls: AtomicUsize,
+    }
+
+    impl ModelBackend for ColdStartBackend {
+        fn format(&self) -> ModelFormat {
+            ModelFormat::TorchScript
+        }
+
+        fn forward(&self, input: &Tensor) -> Result<Tensor> {
+            let delay = if self.calls.fetch_add(1, Ordering::SeqCst) == 0 { 200 } else { 5 };
+            std::thread::sleep(Duration::from_millis(delay));
+            Ok(input.shallow_clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warmup_absorbs_first_call_latency() {
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(MockModelStorage::new()));
+        let backend = Arc::new(ColdStartBackend { calls: AtomicUsize::new(0) });
+        loader.loaded_models.write().await.insert("cold".to_string(), LoadedModel {
+            model: backend.clone(),
+            metadata: ModelMetadata {
+                id: "cold".to_string(),
+                version: "1".to_string(),
+                task_type: "test".to_string(),
+                input_shape: vec![1, 4],
+                output_shape: vec![1, 4],
+                format: None,
+                sha256: None,
+            },
+            last_used: AtomicU64::new(0),
+            path: PathBuf::from("cold.pt"),
+            fingerprint: None,
+        });
+
+        loader.warmup("cold").await.unwrap();
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 1);
+
+        let model = loader.load_model("cold").await.unwrap();
+        let input = Tensor::zeros(&[1, 4], (Kind::Float, Device::Cpu));
+        let timed_forward = || {
+            let start = Instant::now();
+            model.forward(&input).unwrap();
+            start.elapsed()
+        };
+        let first = timed_forward();
+        let second = timed_forward();
+
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 3);
+        assert!(first < second + Duration::from_millis(50), "first inference took {:?}, later ones {:?}", first, second);
+    }
+
+    #[tokio::test]
+    async fn test_warmup_rejects_dynamic_input_dims() {
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(MockModelStorage::new()));
+        let backend = Arc::new(ColdStartBackend { calls: AtomicUsize::new(0) });
+        loader.loaded_models.write().await.insert("dynamic".to_string(), LoadedModel {
+            model: backend.clone(),
+            metadata: ModelMetadata {
+                id: "dynamic".to_string(),
+                version: "1".to_string(),
+                task_type: "test".to_string(),
+                input_shape: vec![-1, 4],
+                output_shape: vec![-1, 4],
+                format: None,
+                sha256: None,
+            },
+            last_used: AtomicU64::new(0),
+            path: PathBuf::from("dynamic.pt"),
+            fingerprint: None,
+        });
+
+        assert!(loader.warmup("dynamic").await.is_err());
+        assert_eq!(backend.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_warmup_requires_loaded_model() {
+        let config = AIConfig { use_cuda: false, ..AIConfig::default() };
+        let loader = ModelLoader::new(config, Arc::new(MockModelStorage::new()));
+        let err = loader.warmup("missing").await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<ModelError>(), Some(ModelError::NotLoaded(_))));
+    }
+}
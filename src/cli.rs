@@ -1,41 +1,127 @@
+use std::path::PathBuf;
 
-use std::env;
-use std::process;
+use clap::{Args, Parser, Subcommand};
 
-/// Parses command-line arguments and returns the specified configuration options.
-///
-/// # Arguments
-/// * None, uses `env::args` internally.
-///
-/// # Returns
-/// * A tuple containing (command, option) if valid arguments are provided.
-/// * Exits the process with an error code if arguments are invalid.
-pub fn parse_cli_args() -> (String, Option<String>) {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 2 {
-        eprintln!("Usage: omnitensor-node <command> [option]");
-        process::exit(1);
-    }
+use crate::event_feed::DEFAULT_EVENTS_ADDR;
+use crate::event_sink::DEFAULT_SUBJECT_PREFIX;
+use crate::identity::DEFAULT_KEY_DIR;
+use crate::logging::LogFormat;
+use crate::role::NodeRole;
 
-    let command = args[1].clone();
-    let option = if args.len() > 2 { Some(args[2].clone()) } else { None };
+/// Command-line interface of the node binary.
+#[derive(Debug, Parser)]
+#[command(name = "omnitensor-node", version, author = "OmniTensor Team", about = "Decentralized AI Infrastructure Node")]
+pub struct Cli {
+    /// Sets a custom config file
+    #[arg(short, long, value_name = "FILE", default_value = "config/default.toml", global = true)]
+    pub config: PathBuf,
 
-    (command, option)
+    /// Log verbosity, with optional per-module overrides such as
+    /// `info,network=debug`; RUST_LOG takes precedence when set
+    #[arg(long, value_name = "DIRECTIVES", global = true)]
+    pub log_level: Option<String>,
+
+    /// Format of log lines; overrides `format` in the config file's
+    /// `[logging]` table, and defaults to `pretty` if neither is set
+    #[arg(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Start the node
+    Start(StartArgs),
+    /// Print version information
+    Version,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct StartArgs {
+    /// Which subsystems the node runs; overrides `role` in the config
+    /// file's `[node]` table, and defaults to `full` if neither is set
+    #[arg(long, value_enum)]
+    pub role: Option<NodeRole>,
+    /// How long to wait for running tasks to finish on shutdown
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub drain_timeout: u64,
+    /// Address to serve the WebSocket event feed on
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_EVENTS_ADDR)]
+    pub events_addr: String,
+    /// Directory holding the node's identity key; created on first start
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_KEY_DIR)]
+    pub key_dir: PathBuf,
+    /// NATS server to mirror node events to, e.g. nats://127.0.0.1:4222
+    #[arg(long, value_name = "URL")]
+    pub nats_url: Option<String>,
+    /// Subject prefix for mirrored events; each goes to <PREFIX>.<event type>
+    #[arg(long, value_name = "PREFIX", default_value = DEFAULT_SUBJECT_PREFIX)]
+    pub nats_subject_prefix: String,
+}
+
+/// Parses the process arguments into a `Cli`.
+///
+/// # Returns
+/// * The parsed `Cli` if the arguments are valid.
+/// * Prints the generated help and exits the process if they are not.
+pub fn parse_cli_args() -> Cli {
+    Cli::parse()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("omnitensor-node").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_start() {
+        let cli = parse(&["start"]).unwrap();
+        assert_eq!(cli.command, Command::Start(StartArgs {
+            role: None,
+            drain_timeout: 30,
+            events_addr: DEFAULT_EVENTS_ADDR.to_string(),
+            key_dir: PathBuf::from(DEFAULT_KEY_DIR),
+            nats_url: None,
+            nats_subject_prefix: DEFAULT_SUBJECT_PREFIX.to_string(),
+        }));
+        assert_eq!(cli.config, PathBuf::from("config/default.toml"));
+        assert_eq!(cli.log_format, None);
+
+        let cli = parse(&["--config", "config/node_config.toml", "start", "--role", "compute-only", "--drain-timeout", "5", "--events-addr", "127.0.0.1:4032", "--key-dir", "/var/lib/omnitensor/keys", "--nats-url", "nats://127.0.0.1:4222", "--nats-subject-prefix", "acme.node1"]).unwrap();
+        assert_eq!(cli.config, PathBuf::from("config/node_config.toml"));
+        assert_eq!(parse(&["start", "--log-format", "json"]).unwrap().log_format, Some(LogFormat::Json));
+        assert_eq!(parse(&["--log-level", "info,network=debug", "start"]).unwrap().log_level.as_deref(), Some("info,network=debug"));
+        assert_eq!(cli.command, Command::Start(StartArgs {
+            role: Some(NodeRole::ComputeOnly),
+            drain_timeout: 5,
+            events_addr: "127.0.0.1:4032".to_string(),
+            key_dir: PathBuf::from("/var/lib/omnitensor/keys"),
+            nats_url: Some("nats://127.0.0.1:4222".to_string()),
+            nats_subject_prefix: "acme.node1".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse(&["version"]).unwrap().command, Command::Version);
+    }
 
     #[test]
-    fn test_parse_cli_args() {
-        env::set_var("RUST_BACKTRACE", "1");
-        env::set_var("TEST_ARGV_0", "omnitensor-node");
-        env::set_var("TEST_ARGV_1", "start");
-        let (cmd, opt) = parse_cli_args();
-        assert_eq!(cmd, "start");
-        assert!(opt.is_none());
+    fn test_invalid_usage() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["launch"]).is_err());
+        assert!(parse(&["start", "--role", "observer"]).is_err());
+        assert!(parse(&["peers"]).is_err());
     }
 }
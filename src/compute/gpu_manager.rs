@@ -1,19 +1,194 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use anyhow::{Result, Context};
-use log::{info, error, debug};
-use crate::models::ComputeTask;
+use log::{info, warn, error, debug};
+use crate::compute::task_scheduler::ComputeTask;
 use crate::config::GPUConfig;
 use crate::utils::gpu::{GPUDevice, GPUMemoryInfo};
 
+const TASK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const HEALTH_COOLDOWN: Duration = Duration::from_secs(60);
+/// A throttled device resumes once it cools this far below `max_temp_celsius`.
+const THERMAL_HYSTERESIS_CELSIUS: f32 = 5.0;
+
+/// Limits applied when dispatching tasks to devices.
+#[derive(Debug, Clone)]
+pub struct DispatchConfig {
+    /// Devices hotter than this stop receiving tasks until they cool down.
+    pub max_temp_celsius: f32,
+    /// Concurrent tasks at which a device's slots count as fully in use.
+    pub task_slots_per_device: usize,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            max_temp_celsius: 85.0,
+            task_slots_per_device: 4,
+        }
+    }
+}
+
+/// Index of a device in the manager's pool.
+pub type DeviceId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthState {
+    Healthy,
+    Unhealthy,
+}
+
+/// Per-device snapshot returned by `GPUManager::get_gpu_stats`.
+#[derive(Debug, Clone)]
+pub struct GPUStats {
+    pub memory: GPUMemoryInfo,
+    /// Tasks currently dispatched to the device.
+    pub active_tasks: usize,
+    /// Share of the device's task slots in use, from 0 to 100. This reflects
+    /// dispatched tasks, not the device's compute utilization.
+    pub utilization_pct: f32,
+}
+
+/// A pooled GPU together with the number of tasks currently dispatched to it.
+/// The counter is shared with outstanding leases so that load is recorded on
+/// the pooled device rather than on a detached clone.
+struct ManagedDevice {
+    device: GPUDevice,
+    active_tasks: Arc<AtomicUsize>,
+    /// Memory promised to leased tasks, which they may not have allocated yet.
+    committed_memory: Arc<AtomicU64>,
+    reserved: Arc<AtomicBool>,
+    last_failure: Option<Instant>,
+    throttled: bool,
+    throttle_events: u64,
+}
+
+impl ManagedDevice {
+    /// Free memory in bytes, or zero if the device can't report it.
+    fn free_memory(&self) -> u64 {
+        self.device.memory_info()
+            .map(|info| info.total.saturating_sub(info.used))
+            .unwrap_or(0)
+    }
+
+    /// Free memory not yet promised to a leased task.
+    fn available_memory(&self) -> u64 {
+        self.free_memory().saturating_sub(self.committed_memory.load(Ordering::SeqCst))
+    }
+
+    /// Whether the shared task queue may dispatch to this device.
+    fn is_dispatchable(&self) -> bool {
+        self.health() == HealthState::Healthy && !self.throttled && !self.reserved.load(Ordering::SeqCst)
+    }
+
+    fn health(&self) -> HealthState {
+        if self.last_failure.is_some() {
+            HealthState::Unhealthy
+        } else {
+            HealthState::Healthy
+        }
+    }
+
+    /// Stops dispatch to a device above `max_temp_celsius` and resumes it once it
+    /// has cooled below the hysteresis threshold.
+    fn record_temperature(&mut self, id: DeviceId, temp_celsius: f32, max_temp_celsius: f32) {
+        if !self.throttled && temp_celsius > max_temp_celsius {
+            self.throttled = true;
+            self.throttle_events += 1;
+            warn!(
+                "GPU device {} ({}) at {:.1}°C exceeds {:.1}°C, throttling (event #{})",
+                id, self.device.name(), temp_celsius, max_temp_celsius, self.throttle_events
+            );
+        } else if self.throttled && temp_celsius < max_temp_celsius - THERMAL_HYSTERESIS_CELSIUS {
+            self.throttled = false;
+            info!("GPU device {} ({}) cooled to {:.1}°C, resuming dispatch", id, self.device.name(), temp_celsius);
+        }
+    }
+
+    /// Applies the outcome of a health probe. A failing device is excluded at
+    /// once; it is only re-included after a passing probe once `cooldown` has
+    /// elapsed since its last failure.
+    fn record_probe(&mut self, id: DeviceId, probe: Result<()>, cooldown: Duration) {
+        match probe {
+            Err(e) => {
+                if self.last_failure.is_none() {
+                    warn!("GPU device {} ({}) failed health check, excluding it: {}", id, self.device.name(), e);
+                }
+                self.last_failure = Some(Instant::now());
+            },
+            Ok(()) => {
+                if let Some(failed_at) = self.last_failure {
+                    if failed_at.elapsed() >= cooldown {
+                        info!("GPU device {} ({}) recovered, re-including it", id, self.device.name());
+                        self.last_failure = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Claim on a pooled device for the duration of one task. Dropping the lease
+/// releases the device's slot and the memory committed to the task.
+pub struct DeviceLease {
+    index: usize,
+    device: GPUDevice,
+    active_tasks: Arc<AtomicUsize>,
+    required_memory: u64,
+    committed_memory: Arc<AtomicU64>,
+}
+
+impl Drop for DeviceLease {
+    fn drop(&mut self) {
+        self.active_tasks.fetch_sub(1, Ordering::SeqCst);
+        self.committed_memory.fetch_sub(self.required_memory, Ordering::SeqCst);
+    }
+}
+
+/// Exclusive hold on a whole device for long-running jobs. While it is alive
+/// the device is withheld from the shared task queue; dropping it returns the
+/// device to the pool.
+pub struct GpuReservation {
+    index: DeviceId,
+    device: GPUDevice,
+    reserved: Arc<AtomicBool>,
+}
+
+impl GpuReservation {
+    pub fn device_id(&self) -> DeviceId {
+        self.index
+    }
+
+    pub fn device(&self) -> &GPUDevice {
+        &self.device
+    }
+}
+
+impl Drop for GpuReservation {
+    fn drop(&mut self) {
+        self.reserved.store(false, Ordering::SeqCst);
+        info!("Released reservation on GPU device {}", self.index);
+    }
+}
+
 pub struct GPUManager {
-    devices: Arc<Mutex<Vec<GPUDevice>>>,
+    devices: Arc<Mutex<Vec<ManagedDevice>>>,
     task_queue: mpsc::Sender<ComputeTask>,
     config: GPUConfig,
+    dispatch: DispatchConfig,
 }
 
 impl GPUManager {
     pub async fn new(config: GPUConfig) -> Result<Self> {
+        Self::with_dispatch_config(config, DispatchConfig::default()).await
+    }
+
+    pub async fn with_dispatch_config(config: GPUConfig, dispatch: DispatchConfig) -> Result<Self> {
         let (tx, rx) = mpsc::channel(100);
         let devices = Arc::new(Mutex::new(Vec::new()));
         
@@ -23,22 +198,32 @@ impl GPUManager {
             devices,
             task_queue: tx,
             config,
+            dispatch,
         };
 
         tokio::spawn(Self::process_task_queue(Arc::clone(&manager.devices), rx));
+        tokio::spawn(Self::monitor_health(Arc::clone(&manager.devices), manager.dispatch.max_temp_celsius));
 
         Ok(manager)
     }
 
-    async fn initialize_devices(devices: &Arc<Mutex<Vec<GPUDevice>>>, config: &GPUConfig) -> Result<()> {
+    async fn initialize_devices(devices: &Arc<Mutex<Vec<ManagedDevice>>>, config: &GPUConfig) -> Result<()> {
         let available_devices = GPUDevice::enumerate().context("Failed to enumerate GPU devices")?;
         
         let mut locked_devices = devices.lock().map_err(|_| anyhow::anyhow!("Failed to acquire lock on devices"))?;
         
         for device in available_devices {
             if device.memory() >= config.min_memory {
-                locked_devices.push(device);
                 info!("Initialized GPU device: {}", device.name());
+                locked_devices.push(ManagedDevice {
+                    device,
+                    active_tasks: Arc::new(AtomicUsize::new(0)),
+                    committed_memory: Arc::new(AtomicU64::new(0)),
+                    reserved: Arc::new(AtomicBool::new(false)),
+                    last_failure: None,
+                    throttled: false,
+                    throttle_events: 0,
+                });
             }
         }
 
@@ -50,44 +235,181 @@ impl GPUManager {
         Ok(())
     }
 
+    /// Queues a task for execution. Tasks that need more memory than any device
+    /// in the pool has in total are rejected up front rather than queued forever.
     pub async fn submit_task(&self, task: ComputeTask) -> Result<()> {
+        let largest_device = self.devices.lock()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire lock on devices"))?
+            .iter()
+            .map(|d| d.device.memory())
+            .max()
+            .unwrap_or(0);
+        if task.required_memory > largest_device {
+            return Err(anyhow::anyhow!(
+                "Task {} requires {} bytes of GPU memory but the largest device has {}",
+                task.id, task.required_memory, largest_device
+            ));
+        }
+
         self.task_queue.send(task).await
             .context("Failed to submit task to GPU queue")?;
         Ok(())
     }
 
-    async fn process_task_queue(devices: Arc<Mutex<Vec<GPUDevice>>>, mut rx: mpsc::Receiver<ComputeTask>) {
-        while let Some(task) = rx.recv().await {
-            let device = Self::select_available_device(&devices).await;
-            
-            match device {
-                Some(mut gpu) => {
-                    if let Err(e) = gpu.execute_task(task).await {
-                        error!("Failed to execute task on GPU: {}", e);
-                    }
+    async fn process_task_queue(devices: Arc<Mutex<Vec<ManagedDevice>>>, mut rx: mpsc::Receiver<ComputeTask>) {
+        let mut waiting = VecDeque::new();
+        let mut retry = tokio::time::interval(TASK_RETRY_INTERVAL);
+
+        loop {
+            tokio::select! {
+                received = rx.recv() => match received {
+                    Some(task) => waiting.push_back(task),
+                    None => break,
+                },
+                _ = retry.tick(), if !waiting.is_empty() => {}
+            }
+
+            Self::dispatch_waiting(&devices, &mut waiting).await;
+        }
+    }
+
+    /// Starts every waiting task that fits on a device right now, keeping the
+    /// rest queued in their original order.
+    async fn dispatch_waiting(devices: &Arc<Mutex<Vec<ManagedDevice>>>, waiting: &mut VecDeque<ComputeTask>) {
+        let mut still_waiting = VecDeque::new();
+
+        while let Some(task) = waiting.pop_front() {
+            match Self::select_available_device(devices, task.required_memory).await {
+                Some(mut lease) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = lease.device.execute_task(task).await {
+                            error!("Failed to execute task on GPU {}: {}", lease.index, e);
+                        }
+                    });
                 },
                 None => {
-                    debug!("No available GPU device, task queued");
-                    // Implement queuing logic here
+                    debug!("No GPU device with {} bytes free, task {} queued", task.required_memory, task.id);
+                    still_waiting.push_back(task);
                 }
             }
         }
+
+        *waiting = still_waiting;
     }
 
-    async fn select_available_device(devices: &Arc<Mutex<Vec<GPUDevice>>>) -> Option<GPUDevice> {
+    /// Picks the device with the fewest dispatched tasks among those with at
+    /// least `required_memory` bytes free and not committed to other leases. The
+    /// new task and its memory are recorded against the device while the pool is
+    /// still locked, so concurrent callers see the updated load.
+    async fn select_available_device(devices: &Arc<Mutex<Vec<ManagedDevice>>>, required_memory: u64) -> Option<DeviceLease> {
         let locked_devices = devices.lock().ok()?;
-        locked_devices.iter()
-            .min_by_key(|d| d.current_load())
-            .cloned()
+        let (index, managed) = locked_devices.iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_dispatchable())
+            .filter(|(_, d)| d.available_memory() >= required_memory)
+            .min_by_key(|(_, d)| (d.active_tasks.load(Ordering::SeqCst), d.device.current_load()))?;
+
+        managed.active_tasks.fetch_add(1, Ordering::SeqCst);
+        managed.committed_memory.fetch_add(required_memory, Ordering::SeqCst);
+        Some(DeviceLease {
+            index,
+            device: managed.device.clone(),
+            active_tasks: Arc::clone(&managed.active_tasks),
+            required_memory,
+            committed_memory: Arc::clone(&managed.committed_memory),
+        })
     }
 
-    pub async fn get_gpu_stats(&self) -> Result<Vec<GPUMemoryInfo>> {
+    /// Takes a device with at least `required_memory` bytes free and not
+    /// committed to leases out of the general pool, preferring the least busy
+    /// one. Tasks already running on it are left to finish.
+    pub async fn reserve_device(&self, required_memory: u64) -> Result<GpuReservation> {
+        let locked_devices = self.devices.lock()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire lock on devices"))?;
+
+        let (index, managed) = locked_devices.iter()
+            .enumerate()
+            .filter(|(_, d)| d.is_dispatchable())
+            .filter(|(_, d)| d.available_memory() >= required_memory)
+            .min_by_key(|(_, d)| d.active_tasks.load(Ordering::SeqCst))
+            .ok_or_else(|| anyhow::anyhow!("No GPU device available to reserve with {} bytes free", required_memory))?;
+
+        managed.reserved.store(true, Ordering::SeqCst);
+        info!("Reserved GPU device {} ({})", index, managed.device.name());
+
+        Ok(GpuReservation {
+            index,
+            device: managed.device.clone(),
+            reserved: Arc::clone(&managed.reserved),
+        })
+    }
+
+    async fn monitor_health(devices: Arc<Mutex<Vec<ManagedDevice>>>, max_temp_celsius: f32) {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            Self::check_device_health(&devices, HEALTH_COOLDOWN, max_temp_celsius).await;
+        }
+    }
+
+    /// Probes every device for a timely, successful memory-info and temperature
+    /// query. The lock is not held while probing so a wedged device can't stall
+    /// dispatch.
+    async fn check_device_health(devices: &Arc<Mutex<Vec<ManagedDevice>>>, cooldown: Duration, max_temp_celsius: f32) {
+        let snapshot: Vec<GPUDevice> = match devices.lock() {
+            Ok(locked_devices) => locked_devices.iter().map(|d| d.device.clone()).collect(),
+            Err(_) => return,
+        };
+
+        let mut probes = Vec::with_capacity(snapshot.len());
+        for device in snapshot {
+            let probe = tokio::time::timeout(
+                HEALTH_PROBE_TIMEOUT,
+                tokio::task::spawn_blocking(move || -> Result<f32> {
+                    device.memory_info().context("Memory info query failed")?;
+                    device.temperature_celsius().context("Temperature query failed")
+                }),
+            ).await;
+            probes.push(match probe {
+                Ok(Ok(result)) => result,
+                Ok(Err(e)) => Err(anyhow::anyhow!("Health probe panicked: {}", e)),
+                Err(_) => Err(anyhow::anyhow!("Device unresponsive for {:?}", HEALTH_PROBE_TIMEOUT)),
+            });
+        }
+
+        if let Ok(mut locked_devices) = devices.lock() {
+            for (id, (managed, probe)) in locked_devices.iter_mut().zip(probes).enumerate() {
+                match probe {
+                    Ok(temp_celsius) => {
+                        managed.record_temperature(id, temp_celsius, max_temp_celsius);
+                        managed.record_probe(id, Ok(()), cooldown);
+                    },
+                    Err(e) => managed.record_probe(id, Err(e), cooldown),
+                }
+            }
+        }
+    }
+
+    pub fn device_health(&self) -> Vec<(DeviceId, HealthState)> {
+        match self.devices.lock() {
+            Ok(locked_devices) => locked_devices.iter().map(|d| d.health()).enumerate().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub async fn get_gpu_stats(&self) -> Result<Vec<GPUStats>> {
         let locked_devices = self.devices.lock()
             .map_err(|_| anyhow::anyhow!("Failed to acquire lock on devices"))?;
         
+        let slots = self.dispatch.task_slots_per_device.max(1);
         let mut stats = Vec::new();
-        for device in locked_devices.iter() {
-            stats.push(device.memory_info().context("Failed to get GPU memory info")?);
+        for managed in locked_devices.iter() {
+            let active_tasks = managed.active_tasks.load(Ordering::SeqCst);
+            stats.push(GPUStats {
+                memory: managed.device.memory_info().context("Failed to get GPU memory info")?,
+                active_tasks,
+                utilization_pct: (active_tasks.min(slots) as f32 / slots as f32) * 100.0,
+            });
         }
 
         Ok(stats)
@@ -98,10 +420,30 @@ impl GPUManager {
 mod tests {
     use super::*;
     use tokio::time::{timeout, Duration};
+    use crate::compute::task_scheduler::{new_trace_id, RetryPolicy};
+
+    fn test_config() -> GPUConfig {
+        GPUConfig { min_memory: 4 * 1024 * 1024 * 1024 } // 4 GB
+    }
+
+    fn task_requiring(id: &str, required_memory: u64) -> ComputeTask {
+        ComputeTask {
+            id: id.to_string(),
+            model_id: "test_model".to_string(),
+            input_data: vec![1, 2, 3],
+            priority: 1,
+            max_duration: Duration::from_secs(60),
+            retry_policy: RetryPolicy::default(),
+            attempt: 0,
+            required_memory,
+            trace_id: new_trace_id(),
+            callback_url: None,
+        }
+    }
 
     #[tokio::test]
     async fn test_gpu_manager_initialization() {
-        let config = GPUConfig { min_memory: 4 * 1024 * 1024 * 1024 }; // 4 GB
+        let config = test_config();
         let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
         
         let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
@@ -110,10 +452,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_task_submission() {
-        let config = GPUConfig { min_memory: 4 * 1024 * 1024 * 1024 }; // 4 GB
+        let config = test_config();
         let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
         
-        let task = ComputeTask::new("test_task", vec![1, 2, 3]);
+        let task = task_requiring("test_task", 0);
         manager.submit_task(task).await.expect("Failed to submit task");
 
         // Allow some time for task processing
@@ -126,15 +468,179 @@ mod tests {
 
     #[tokio::test]
     async fn test_gpu_stats() {
-        let config = GPUConfig { min_memory: 4 * 1024 * 1024 * 1024 }; // 4 GB
+        let config = test_config();
         let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
         
         let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
         assert!(!stats.is_empty(), "No GPU stats available");
         
         for stat in stats {
-            assert!(stat.total > 0, "Invalid total memory");
-            assert!(stat.used <= stat.total, "Used memory exceeds total memory");
+            assert!(stat.memory.total > 0, "Invalid total memory");
+            assert!(stat.memory.used <= stat.memory.total, "Used memory exceeds total memory");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_selection_spreads_across_devices() {
+        let config = test_config();
+        let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
+        let device_count = manager.get_gpu_stats().await.expect("Failed to get GPU stats").len();
+
+        let selections = (0..device_count).map(|_| GPUManager::select_available_device(&manager.devices, 0));
+        let leases: Vec<DeviceLease> = futures::future::join_all(selections).await
+            .into_iter()
+            .map(|lease| lease.expect("No device selected"))
+            .collect();
+
+        let mut indices: Vec<usize> = leases.iter().map(|lease| lease.index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        assert_eq!(indices.len(), device_count, "Busy device was assigned twice");
+
+        drop(leases);
+        let locked_devices = manager.devices.lock().unwrap();
+        assert!(locked_devices.iter().all(|d| d.active_tasks.load(Ordering::SeqCst) == 0));
+    }
+
+    #[tokio::test]
+    async fn test_failing_device_excluded_then_restored() {
+        let config = test_config();
+        let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
+        let cooldown = Duration::from_millis(100);
+
+        manager.devices.lock().unwrap()[0].record_probe(0, Err(anyhow::anyhow!("memory info failed")), cooldown);
+        assert_eq!(manager.device_health()[0], (0, HealthState::Unhealthy));
+
+        let device_count = manager.device_health().len();
+        let leases: Vec<DeviceLease> = futures::future::join_all(
+            (0..device_count).map(|_| GPUManager::select_available_device(&manager.devices, 0))
+        ).await.into_iter().flatten().collect();
+        assert!(leases.iter().all(|lease| lease.index != 0), "Unhealthy device was selected");
+        drop(leases);
+
+        // A passing probe inside the cooldown window keeps the device excluded.
+        manager.devices.lock().unwrap()[0].record_probe(0, Ok(()), cooldown);
+        assert_eq!(manager.device_health()[0], (0, HealthState::Unhealthy));
+
+        tokio::time::sleep(cooldown).await;
+        GPUManager::check_device_health(&manager.devices, cooldown, f32::MAX).await;
+        assert_eq!(manager.device_health()[0], (0, HealthState::Healthy));
+    }
+
+    #[tokio::test]
+    async fn test_task_exceeding_device_memory_rejected() {
+        let config = test_config();
+        let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
+
+        let result = manager.submit_task(task_requiring("huge_task", u64::MAX)).await;
+        assert!(result.is_err(), "Oversized task should be rejected");
+    }
+
+    #[tokio::test]
+    async fn test_task_routed_to_device_with_enough_free_memory() {
+        let config = test_config();
+        let manager = GPUManager::new(config).await.expect("Failed to initialize GPUManager");
+
+        let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
+        let most_free = stats.iter().map(|s| s.memory.total - s.memory.used).max().unwrap();
+
+        let lease = GPUManager::select_available_device(&manager.devices, most_free).await
+            .expect("No device with enough free memory");
+        let info = lease.device.memory_info().expect("Failed to get GPU memory info");
+        assert!(info.total - info.used >= most_free);
+    }
+
+    #[tokio::test]
+    async fn test_leased_memory_not_promised_twice() {
+        let manager = GPUManager::new(test_config()).await.expect("Failed to initialize GPUManager");
+        let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
+        let most_free = stats.iter().map(|s| s.memory.total - s.memory.used).max().unwrap();
+
+        let first = GPUManager::select_available_device(&manager.devices, most_free).await
+            .expect("No device with enough free memory");
+        // The memory is committed to the first lease until it's dropped
+        let second = GPUManager::select_available_device(&manager.devices, most_free).await;
+        assert!(second.as_ref().map_or(true, |lease| lease.index != first.index), "Committed memory leased twice");
+        drop(second);
+
+        let index = first.index;
+        assert_eq!(manager.devices.lock().unwrap()[index].committed_memory.load(Ordering::SeqCst), most_free);
+        drop(first);
+        assert_eq!(manager.devices.lock().unwrap()[index].committed_memory.load(Ordering::SeqCst), 0);
+        assert!(GPUManager::select_available_device(&manager.devices, most_free).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hot_device_throttled_until_cooled() {
+        let dispatch = DispatchConfig { max_temp_celsius: 80.0, ..DispatchConfig::default() };
+        let manager = GPUManager::with_dispatch_config(test_config(), dispatch).await.expect("Failed to initialize GPUManager");
+        let device_count = manager.device_health().len();
+        let max_temp = manager.dispatch.max_temp_celsius;
+        assert_eq!(max_temp, 80.0);
+
+        let select_all = || futures::future::join_all(
+            (0..device_count).map(|_| GPUManager::select_available_device(&manager.devices, 0))
+        );
+
+        manager.devices.lock().unwrap()[0].record_temperature(0, 95.0, max_temp);
+        let leases: Vec<DeviceLease> = select_all().await.into_iter().flatten().collect();
+        assert!(leases.iter().all(|lease| lease.index != 0), "Task dispatched to hot device");
+        drop(leases);
+
+        // Still within the hysteresis band, so the device stays throttled.
+        manager.devices.lock().unwrap()[0].record_temperature(0, max_temp - 1.0, max_temp);
+        let leases: Vec<DeviceLease> = select_all().await.into_iter().flatten().collect();
+        assert!(leases.iter().all(|lease| lease.index != 0), "Task dispatched before device cooled");
+        drop(leases);
+
+        manager.devices.lock().unwrap()[0].record_temperature(0, 60.0, max_temp);
+        let leases: Vec<DeviceLease> = select_all().await.into_iter().flatten().collect();
+        assert!(leases.iter().any(|lease| lease.index == 0), "Cooled device not resumed");
+        assert_eq!(manager.devices.lock().unwrap()[0].throttle_events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_slot_utilization_tracks_active_tasks() {
+        let dispatch = DispatchConfig { task_slots_per_device: 2, ..DispatchConfig::default() };
+        let manager = GPUManager::with_dispatch_config(test_config(), dispatch).await.expect("Failed to initialize GPUManager");
+
+        let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
+        assert!(stats.iter().all(|s| s.active_tasks == 0 && s.utilization_pct == 0.0));
+
+        let leases: Vec<DeviceLease> = futures::future::join_all(
+            (0..2).map(|_| GPUManager::select_available_device(&manager.devices, 0))
+        ).await.into_iter().flatten().collect();
+
+        let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
+        assert_eq!(stats.iter().map(|s| s.active_tasks).sum::<usize>(), leases.len());
+        // Each task takes one of the device's two slots
+        for stat in &stats {
+            assert_eq!(stat.utilization_pct, stat.active_tasks as f32 * 50.0);
         }
+
+        drop(leases);
+        let stats = manager.get_gpu_stats().await.expect("Failed to get GPU stats");
+        assert!(stats.iter().all(|s| s.active_tasks == 0 && s.utilization_pct == 0.0));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_reserved_device_excluded_until_released() {
+        let manager = GPUManager::new(test_config()).await.expect("Failed to initialize GPUManager");
+        let device_count = manager.device_health().len();
+
+        let reservation = manager.reserve_device(0).await.expect("Failed to reserve device");
+        let reserved_id = reservation.device_id();
+
+        let leases: Vec<DeviceLease> = futures::future::join_all(
+            (0..device_count).map(|_| GPUManager::select_available_device(&manager.devices, 0))
+        ).await.into_iter().flatten().collect();
+        assert!(leases.iter().all(|lease| lease.index != reserved_id), "Reserved device was selected");
+        drop(leases);
+
+        drop(reservation);
+        let leases: Vec<DeviceLease> = futures::future::join_all(
+            (0..device_count).map(|_| GPUManager::select_available_device(&manager.devices, 0))
+        ).await.into_iter().flatten().collect();
+        assert!(leases.iter().any(|lease| lease.index == reserved_id), "Released device not returned to pool");
+    }
+}
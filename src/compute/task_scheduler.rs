@@ -1,13 +1,19 @@
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, Notify, OwnedSemaphorePermit, Semaphore};
 use tokio::time::{Duration, Instant};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::Instrument;
 
 use crate::compute::gpu_manager::GpuManager;
 use crate::ai::model_loader::ModelLoader;
 use crate::error::OmniTensorError;
 use crate::metrics::MetricsCollector;
+use crate::webhook::{TaskNotification, WebhookNotifier};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComputeTask {
@@ -16,8 +22,122 @@ pub struct ComputeTask {
     pub input_data: Vec<u8>,
     pub priority: u8,
     pub max_duration: Duration,
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// Number of retries already spent on this task.
+    #[serde(default)]
+    pub attempt: u32,
+    /// GPU memory in bytes the task needs free on the device it runs on.
+    #[serde(default)]
+    pub required_memory: u64,
+    /// Correlation id attached to every log line about this task, across nodes.
+    #[serde(default = "new_trace_id")]
+    pub trace_id: String,
+    /// Webhook notified once the task completes or finally fails.
+    #[serde(default)]
+    pub callback_url: Option<String>,
 }
 
+pub fn new_trace_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Span that ties together logs from a task's submission through its execution.
+fn task_span(task: &ComputeTask) -> tracing::Span {
+    tracing::info_span!("task", task_id = %task.id, trace_id = %task.trace_id)
+}
+
+/// Hex-encoded SHA-256 of a task's output.
+pub fn result_hash(output: &[u8]) -> String {
+    hex::encode(Sha256::digest(output))
+}
+
+/// Where a task is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Queued,
+    Running,
+    Completed { result_hash: String },
+    Failed { error: String },
+    Cancelled,
+}
+
+impl TaskState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TaskState::Completed { .. } | TaskState::Failed { .. } | TaskState::Cancelled)
+    }
+}
+
+/// Published on every task state change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskStatusUpdate {
+    pub task_id: String,
+    pub state: TaskState,
+}
+
+/// States of finished tasks are kept for this many tasks before the oldest are forgotten.
+const MAX_FINISHED_STATES: usize = 10_000;
+
+#[derive(Default)]
+struct TaskStates {
+    states: HashMap<String, TaskState>,
+    finished: VecDeque<String>,
+}
+
+/// How often a failed task is requeued before its failure is surfaced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt + 1`, doubling on every attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.base_backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+/// Heap entry wrapping a queued task. Higher priority is popped first; tasks of
+/// equal priority keep their submission order.
+struct QueuedTask {
+    task: ComputeTask,
+    seq: u64,
+}
+
+impl PartialEq for QueuedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedTask {}
+
+impl PartialOrd for QueuedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedTask {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.task.priority
+            .cmp(&other.task.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TaskResult {
     pub task_id: String,
     pub output: Vec<u8>,
@@ -29,12 +149,114 @@ pub trait TaskExecutor: Send + Sync {
     async fn execute(&self, task: ComputeTask) -> Result<TaskResult, OmniTensorError>;
 }
 
+/// Bounds of the task result cache.
+#[derive(Debug, Clone)]
+pub struct ResultCacheConfig {
+    pub max_entries: usize,
+    pub ttl: Duration,
+    /// Models whose output isn't a pure function of their input, so results
+    /// must never be reused.
+    pub uncached_models: HashSet<String>,
+}
+
+impl Default for ResultCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            ttl: Duration::from_secs(300),
+            uncached_models: HashSet::new(),
+        }
+    }
+}
+
+/// Results of completed tasks keyed by model and input hash, so identical
+/// tasks don't recompute.
+pub struct ResultCache {
+    config: ResultCacheConfig,
+    entries: Mutex<HashMap<(String, String), (Instant, TaskResult)>>,
+}
+
+impl ResultCache {
+    pub fn new(config: ResultCacheConfig) -> Self {
+        Self { config, entries: Mutex::new(HashMap::new()) }
+    }
+
+    fn key(task: &ComputeTask) -> (String, String) {
+        (task.model_id.clone(), hex::encode(Sha256::digest(&task.input_data)))
+    }
+
+    fn is_cacheable(&self, task: &ComputeTask) -> bool {
+        self.config.max_entries > 0 && !self.config.uncached_models.contains(&task.model_id)
+    }
+
+    /// Returns the cached result for a task with the same model and input,
+    /// relabelled with `task`'s id.
+    pub fn get(&self, task: &ComputeTask) -> Option<TaskResult> {
+        if !self.is_cacheable(task) {
+            return None;
+        }
+        let key = Self::key(task);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(&key) {
+            Some((stored_at, result)) if stored_at.elapsed() < self.config.ttl => {
+                Some(TaskResult { task_id: task.id.clone(), ..result.clone() })
+            },
+            Some(_) => {
+                entries.remove(&key);
+                None
+            },
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, task: &ComputeTask, result: &TaskResult) {
+        if !self.is_cacheable(task) {
+            return;
+        }
+        let key = Self::key(task);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let ttl = self.config.ttl;
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            let oldest = entries.iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), result.clone()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 pub struct TaskScheduler {
-    queue: Arc<Mutex<VecDeque<ComputeTask>>>,
+    queue: Arc<Mutex<BinaryHeap<QueuedTask>>>,
+    next_seq: AtomicU64,
     gpu_manager: Arc<GpuManager>,
     model_loader: Arc<ModelLoader>,
     metrics: Arc<MetricsCollector>,
-    max_concurrent_tasks: usize,
+    max_concurrent_tasks: AtomicUsize,
+    task_slots: Arc<Semaphore>,
+    /// Slots still owed after shrinking `max_concurrent_tasks`, retired as
+    /// running tasks give theirs back.
+    retiring_slots: Mutex<usize>,
+    running_tasks: AtomicUsize,
+    cancellations: Mutex<HashMap<String, Arc<Notify>>>,
+    /// Failed tasks waiting out their backoff before being requeued.
+    retrying: Mutex<HashMap<String, ComputeTask>>,
+    shutting_down: AtomicBool,
+    result_cache: Option<ResultCache>,
+    webhooks: Option<Arc<WebhookNotifier>>,
+    task_states: Mutex<TaskStates>,
+    status_updates: broadcast::Sender<TaskStatusUpdate>,
 }
 
 impl TaskScheduler {
@@ -45,74 +267,363 @@ impl TaskScheduler {
         max_concurrent_tasks: usize,
     ) -> Self {
         Self {
-            queue: Arc::new(Mutex::new(VecDeque::new())),
+            queue: Arc::new(Mutex::new(BinaryHeap::new())),
+            next_seq: AtomicU64::new(0),
             gpu_manager,
             model_loader,
             metrics,
-            max_concurrent_tasks,
+            max_concurrent_tasks: AtomicUsize::new(max_concurrent_tasks),
+            task_slots: Arc::new(Semaphore::new(max_concurrent_tasks)),
+            retiring_slots: Mutex::new(0),
+            running_tasks: AtomicUsize::new(0),
+            cancellations: Mutex::new(HashMap::new()),
+            retrying: Mutex::new(HashMap::new()),
+            shutting_down: AtomicBool::new(false),
+            result_cache: None,
+            webhooks: None,
+            task_states: Mutex::new(TaskStates::default()),
+            status_updates: broadcast::channel(1024).0,
+        }
+    }
+
+    /// Reuses results of earlier tasks with the same model and input instead
+    /// of executing them again.
+    pub fn with_result_cache(mut self, config: ResultCacheConfig) -> Self {
+        self.result_cache = Some(ResultCache::new(config));
+        self
+    }
+
+    /// Notifies each task's `callback_url`, if it has one, when it finishes.
+    pub fn with_webhooks(mut self, notifier: Arc<WebhookNotifier>) -> Self {
+        self.webhooks = Some(notifier);
+        self
+    }
+
+    fn notify(&self, task: &ComputeTask, notification: TaskNotification) {
+        if let (Some(webhooks), Some(url)) = (&self.webhooks, &task.callback_url) {
+            webhooks.notify(url.clone(), notification);
         }
     }
 
     pub async fn submit_task(&self, task: ComputeTask) -> Result<(), OmniTensorError> {
-        let mut queue = self.queue.lock().map_err(|_| OmniTensorError::LockError)?;
-        queue.push_back(task);
+        let span = task_span(&task);
+        async move {
+            if self.shutting_down.load(AtomicOrdering::SeqCst) {
+                return Err(OmniTensorError::ShuttingDown);
+            }
+            tracing::debug!("Task queued with priority {}", task.priority);
+            self.enqueue(task)
+        }.instrument(span).await
+    }
+
+    fn enqueue(&self, task: ComputeTask) -> Result<(), OmniTensorError> {
+        let seq = self.next_seq.fetch_add(1, AtomicOrdering::Relaxed);
+        let task_id = task.id.clone();
+        self.queue.lock().map_err(|_| OmniTensorError::LockError)?.push(QueuedTask { task, seq });
         self.metrics.increment_queued_tasks();
+        self.set_state(&task_id, TaskState::Queued);
         Ok(())
     }
 
-    pub async fn run(&self) {
+    fn set_state(&self, task_id: &str, state: TaskState) {
+        {
+            let mut task_states = self.task_states.lock().unwrap_or_else(|e| e.into_inner());
+            if state.is_finished() {
+                task_states.finished.push_back(task_id.to_string());
+                while task_states.finished.len() > MAX_FINISHED_STATES {
+                    if let Some(oldest) = task_states.finished.pop_front() {
+                        if task_states.states.get(&oldest).map_or(false, TaskState::is_finished) {
+                            task_states.states.remove(&oldest);
+                        }
+                    }
+                }
+            }
+            task_states.states.insert(task_id.to_string(), state.clone());
+        }
+        // Nobody may be listening
+        let _ = self.status_updates.send(TaskStatusUpdate { task_id: task_id.to_string(), state });
+    }
+
+    /// Current state of a task, if it's known to this scheduler.
+    pub fn task_state(&self, task_id: &str) -> Option<TaskState> {
+        self.task_states.lock().unwrap_or_else(|e| e.into_inner()).states.get(task_id).cloned()
+    }
+
+    /// Receives every task state change from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskStatusUpdate> {
+        self.status_updates.subscribe()
+    }
+
+    /// Cancels a task that is still queued, waiting to be retried, or currently
+    /// executing. Returns `Ok(false)` if the task is unknown or has already
+    /// finished.
+    pub async fn cancel_task(&self, task_id: &str) -> Result<bool, OmniTensorError> {
+        let removed = {
+            let mut queue = self.queue.lock().map_err(|_| OmniTensorError::LockError)?;
+            let (cancelled, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *queue)
+                .into_iter()
+                .partition(|queued| queued.task.id == task_id);
+            *queue = kept.into();
+            cancelled.into_iter().next()
+        };
+
+        if let Some(queued) = removed {
+            self.metrics.decrement_queued_tasks();
+            tracing::info!("Task {} cancelled while queued", task_id);
+            self.set_state(task_id, TaskState::Cancelled);
+            self.notify(&queued.task, TaskNotification::cancelled(task_id));
+            return Ok(true);
+        }
+
+        let retrying = self.retrying.lock().map_err(|_| OmniTensorError::LockError)?.remove(task_id);
+        if let Some(task) = retrying {
+            tracing::info!("Task {} cancelled while waiting to be retried", task_id);
+            self.set_state(task_id, TaskState::Cancelled);
+            self.notify(&task, TaskNotification::cancelled(task_id));
+            return Ok(true);
+        }
+
+        let cancellations = self.cancellations.lock().map_err(|_| OmniTensorError::LockError)?;
+        match cancellations.get(task_id) {
+            Some(cancel) => {
+                cancel.notify_one();
+                tracing::info!("Task {} cancellation requested", task_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Dispatches queued tasks, keeping at most `max_concurrent_tasks` in flight.
+    /// A slot is acquired before a task is dequeued so that the highest-priority
+    /// task is picked at the moment capacity frees up.
+    pub async fn run(self: Arc<Self>) {
         loop {
-            let task = {
-                let mut queue = self.queue.lock().unwrap();
-                queue.pop_front()
+            let permit = match Arc::clone(&self.task_slots).acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => break,
             };
+            // Tasks still queued are left undispatched once draining starts
+            if self.shutting_down.load(AtomicOrdering::SeqCst) {
+                break;
+            }
 
-            if let Some(task) = task {
-                if let Err(e) = self.process_task(task).await {
-                    log::error!("Error processing task: {:?}", e);
-                }
+            if let Some((task, cancel)) = self.next_task() {
+                self.metrics.decrement_queued_tasks();
+                let running = self.running_tasks.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                self.metrics.set_running_tasks(running);
+                self.set_state(&task.id, TaskState::Running);
+
+                let scheduler = Arc::clone(&self);
+                let span = task_span(&task);
+                tokio::spawn(async move {
+                    let outcome = scheduler.process_task(&task, &cancel).await;
+                    scheduler.cancellations.lock().unwrap_or_else(|e| e.into_inner()).remove(&task.id);
+                    match outcome {
+                        Ok(result) => {
+                            let result_hash = result_hash(&result.output);
+                            scheduler.set_state(&task.id, TaskState::Completed { result_hash: result_hash.clone() });
+                            scheduler.notify(&task, TaskNotification::completed(&task.id, result_hash));
+                        },
+                        Err(e) => scheduler.retry_or_fail(task, e).await,
+                    }
+                    let running = scheduler.running_tasks.fetch_sub(1, AtomicOrdering::SeqCst) - 1;
+                    scheduler.metrics.set_running_tasks(running);
+                    scheduler.release_slot(permit);
+                }.instrument(span));
             } else {
+                self.release_slot(permit);
                 tokio::time::sleep(Duration::from_millis(100)).await;
             }
         }
     }
 
-    async fn process_task(&self, task: ComputeTask) -> Result<(), OmniTensorError> {
-        let gpu = self.gpu_manager.acquire_gpu().await?;
-        let model = self.model_loader.load_model(&task.model_id).await?;
+    /// Stops accepting and dispatching tasks, then waits up to `timeout` for the
+    /// ones already running to finish. Returns whether they all finished in time.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.shutting_down.store(true, AtomicOrdering::SeqCst);
+        tracing::info!("Draining {} running task(s)", self.get_running_count());
 
-        let start_time = Instant::now();
-        let result = model.execute(task.input_data).await?;
-        let execution_time = start_time.elapsed();
+        let drained = tokio::time::timeout(timeout, async {
+            while self.get_running_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }).await.is_ok();
 
-        self.metrics.record_task_execution(execution_time);
+        if !drained {
+            tracing::warn!("{} task(s) still running after {:?}", self.get_running_count(), timeout);
+        }
+        drained
+    }
 
-        if execution_time > task.max_duration {
-            log::warn!("Task {} exceeded max duration", task.id);
-            self.metrics.increment_overdue_tasks();
+    /// Changes how many tasks may run at once. Growing takes effect immediately;
+    /// when shrinking, no new task is dispatched until the running ones drop
+    /// below the new limit.
+    pub fn set_max_concurrent_tasks(&self, n: usize) {
+        let mut retiring = self.retiring_slots.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = self.max_concurrent_tasks.swap(n, AtomicOrdering::SeqCst);
+        if n > previous {
+            // Slots not yet retired from an earlier shrink are kept instead
+            let kept = (n - previous).min(*retiring);
+            *retiring -= kept;
+            self.task_slots.add_permits(n - previous - kept);
+        } else if n < previous {
+            // Retire idle slots now, and the rest as running tasks release them
+            let mut excess = previous - n;
+            while excess > 0 {
+                match self.task_slots.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => break,
+                }
+                excess -= 1;
+            }
+            *retiring += excess;
         }
+        tracing::info!("Max concurrent tasks changed from {} to {}", previous, n);
+    }
 
-        self.gpu_manager.release_gpu(gpu).await?;
+    /// Gives a task slot back, unless it's owed to an earlier shrink.
+    fn release_slot(&self, permit: OwnedSemaphorePermit) {
+        let mut retiring = self.retiring_slots.lock().unwrap_or_else(|e| e.into_inner());
+        if *retiring > 0 {
+            *retiring -= 1;
+            permit.forget();
+        }
+    }
+
+    pub fn max_concurrent_tasks(&self) -> usize {
+        self.max_concurrent_tasks.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Requeues a failed task after an exponential backoff, or gives up once its
+    /// retry budget is spent. Cancelled tasks are never retried.
+    async fn retry_or_fail(self: &Arc<Self>, mut task: ComputeTask, error: OmniTensorError) {
+        let cancelled = matches!(error, OmniTensorError::TaskCancelled(_));
+        if cancelled || task.attempt >= task.retry_policy.max_retries {
+            tracing::error!("Error processing task {} after {} attempt(s): {:?}", task.id, task.attempt + 1, error);
+            if cancelled {
+                self.set_state(&task.id, TaskState::Cancelled);
+                self.notify(&task, TaskNotification::cancelled(&task.id));
+            } else {
+                self.set_state(&task.id, TaskState::Failed { error: format!("{:?}", error) });
+                self.notify(&task, TaskNotification::failed(&task.id, format!("{:?}", error)));
+            }
+            return;
+        }
+
+        let backoff = task.retry_policy.backoff(task.attempt);
+        task.attempt += 1;
+        self.metrics.increment_task_retries();
+        tracing::warn!(
+            "Task {} failed: {:?}, retrying in {:?} ({}/{})",
+            task.id, error, backoff, task.attempt, task.retry_policy.max_retries
+        );
+
+        // Parked until the backoff ends, so cancel_task can still find it
+        let task_id = task.id.clone();
+        self.set_state(&task_id, TaskState::Queued);
+        self.retrying.lock().unwrap_or_else(|e| e.into_inner()).insert(task_id.clone(), task);
+        let scheduler = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(backoff).await;
+            let task = scheduler.retrying.lock().unwrap_or_else(|e| e.into_inner()).remove(&task_id);
+            // Gone if it was cancelled during the backoff
+            if let Some(task) = task {
+                let callback_url = task.callback_url.clone();
+                if let Err(e) = scheduler.submit_task(task).await {
+                    tracing::error!("Failed to requeue task {}: {:?}", task_id, e);
+                    let error = format!("{:?}", e);
+                    scheduler.set_state(&task_id, TaskState::Failed { error: error.clone() });
+                    if let (Some(webhooks), Some(url)) = (&scheduler.webhooks, callback_url) {
+                        webhooks.notify(url, TaskNotification::failed(&task_id, error));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Pops the highest-priority task and registers its cancellation handle.
+    /// Registering before the queue lock is released means `cancel_task` always
+    /// finds the task either queued or cancellable.
+    fn next_task(&self) -> Option<(ComputeTask, Arc<Notify>)> {
+        let mut queue = self.queue.lock().unwrap();
+        let task = queue.pop()?.task;
+        let cancel = Arc::new(Notify::new());
+        self.cancellations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(task.id.clone(), Arc::clone(&cancel));
+        Some((task, cancel))
+    }
+
+    async fn process_task(&self, task: &ComputeTask, cancel: &Notify) -> Result<TaskResult, OmniTensorError> {
+        if let Some(result) = self.result_cache.as_ref().and_then(|cache| cache.get(task)) {
+            self.metrics.increment_task_cache_hits();
+            tracing::info!("Task {} served from result cache", task.id);
+            return Ok(result);
+        }
+
+        let result = self.execute_on_gpu(task, cancel).await?;
+        if let Some(cache) = &self.result_cache {
+            cache.insert(task, &result);
+        }
 
         // Here you would typically send the result back to the client or to a result queue
-        log::info!("Task {} completed in {:?}", task.id, execution_time);
+        tracing::info!("Task {} completed in {:?}", task.id, result.execution_time);
 
-        Ok(())
+        Ok(result)
+    }
+
+    async fn execute_on_gpu(&self, task: &ComputeTask, cancel: &Notify) -> Result<TaskResult, OmniTensorError> {
+        let gpu = self.gpu_manager.acquire_gpu().await?;
+        let outcome = tokio::select! {
+            result = self.execute_task(task) => result,
+            _ = cancel.notified() => {
+                tracing::info!("Task {} aborted by cancellation", task.id);
+                Err(OmniTensorError::TaskCancelled(task.id.clone()))
+            }
+        };
+        self.gpu_manager.release_gpu(gpu).await?;
+        outcome
+    }
+
+    /// Runs the task's model, cancelling it once `max_duration` has elapsed.
+    async fn execute_task(&self, task: &ComputeTask) -> Result<TaskResult, OmniTensorError> {
+        let model = self.model_loader.load_model(&task.model_id).await?;
+
+        let start_time = Instant::now();
+        let result = match tokio::time::timeout(task.max_duration, model.execute(task.clone())).await {
+            Ok(result) => result?,
+            Err(_) => {
+                tracing::warn!("Task {} exceeded max duration of {:?}, aborting", task.id, task.max_duration);
+                self.metrics.increment_overdue_tasks();
+                return Err(OmniTensorError::TaskTimeout(task.id.clone()));
+            }
+        };
+        let execution_time = start_time.elapsed();
+
+        self.metrics.record_task_execution(execution_time);
+
+        Ok(TaskResult { execution_time, ..result })
     }
 
     pub async fn get_queue_length(&self) -> usize {
         self.queue.lock().unwrap().len()
     }
+
+    pub fn get_running_count(&self) -> usize {
+        self.running_tasks.load(AtomicOrdering::SeqCst)
+    }
 }
 
+/// Mocks and helpers shared by the scheduler tests.
 #[cfg(test)]
-mod tests {
+pub(crate) mod test_support {
     use super::*;
-    use mockall::predicate::*;
     use mockall::mock;
 
     mock! {
-        GpuManager {}
+        pub GpuManager {}
         #[async_trait]
         impl GpuManager for GpuManager {
             async fn acquire_gpu(&self) -> Result<String, OmniTensorError>;
@@ -121,15 +632,15 @@ mod tests {
     }
 
     mock! {
-        ModelLoader {}
+        pub ModelLoader {}
         #[async_trait]
         impl ModelLoader for ModelLoader {
             async fn load_model(&self, model_id: &str) -> Result<Arc<dyn TaskExecutor>, OmniTensorError>;
         }
     }
 
-    #[tokio::test]
-    async fn test_submit_and_process_task() {
+    /// A GPU manager that always hands out `gpu1`.
+    pub fn idle_gpu_manager() -> MockGpuManager {
         let mut gpu_manager = MockGpuManager::new();
         gpu_manager
             .expect_acquire_gpu()
@@ -137,6 +648,43 @@ mod tests {
         gpu_manager
             .expect_release_gpu()
             .returning(|_| Ok(()));
+        gpu_manager
+    }
+
+    /// A model loader that serves every model with `executor`.
+    pub fn loader_for(executor: Arc<dyn TaskExecutor>) -> MockModelLoader {
+        let mut model_loader = MockModelLoader::new();
+        model_loader
+            .expect_load_model()
+            .returning(move |_| Ok(executor.clone()));
+        model_loader
+    }
+
+    pub fn make_task(id: &str, priority: u8, max_duration: Duration) -> ComputeTask {
+        ComputeTask {
+            id: id.to_string(),
+            model_id: "model1".to_string(),
+            input_data: vec![],
+            priority,
+            max_duration,
+            retry_policy: RetryPolicy::default(),
+            attempt: 0,
+            required_memory: 0,
+            trace_id: format!("trace-{}", id),
+            callback_url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::*;
+    use mockall::predicate::*;
+
+    #[tokio::test]
+    async fn test_submit_and_process_task() {
+        let gpu_manager = idle_gpu_manager();
 
         let mut model_loader = MockModelLoader::new();
         model_loader
@@ -158,6 +706,11 @@ mod tests {
             input_data: vec![1, 2, 3],
             priority: 1,
             max_duration: Duration::from_secs(60),
+            retry_policy: RetryPolicy::default(),
+            attempt: 0,
+            required_memory: 0,
+            trace_id: new_trace_id(),
+            callback_url: None,
         };
 
         scheduler.submit_task(task).await.unwrap();
@@ -165,4 +718,675 @@ mod tests {
 
       
     }
-}
\ No newline at end of file
+
+    struct SlowExecutor {
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for SlowExecutor {
+        async fn execute(&self, task: ComputeTask) -> Result<TaskResult, OmniTensorError> {
+            let now = self.in_flight.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+            self.peak.fetch_max(now, AtomicOrdering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            self.in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+            Ok(TaskResult {
+                task_id: task.id,
+                output: vec![],
+                execution_time: Duration::from_millis(200),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_bounded_by_max_concurrent_tasks() {
+        tokio::time::pause();
+        let gpu_manager = idle_gpu_manager();
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: in_flight.clone(),
+            peak: peak.clone(),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            3,
+        ));
+
+        for i in 0..8 {
+            let task = make_task(&format!("task{}", i), 1, Duration::from_secs(60));
+            scheduler.submit_task(task).await.unwrap();
+        }
+
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(scheduler.get_running_count(), 3);
+        assert_eq!(scheduler.get_queue_length().await, 5);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        handle.abort();
+
+        assert_eq!(peak.load(AtomicOrdering::SeqCst), 3);
+        assert_eq!(scheduler.get_queue_length().await, 0);
+        assert_eq!(scheduler.get_running_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_follows_max_concurrent_tasks_changes() {
+        tokio::time::pause();
+        let gpu_manager = idle_gpu_manager();
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: in_flight.clone(),
+            peak: peak.clone(),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        for i in 0..10 {
+            let task = make_task(&format!("task{}", i), 1, Duration::from_secs(60));
+            scheduler.submit_task(task).await.unwrap();
+        }
+
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(scheduler.get_running_count(), 1);
+
+        // Growing starts queued tasks right away
+        scheduler.set_max_concurrent_tasks(3);
+        assert_eq!(scheduler.max_concurrent_tasks(), 3);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(scheduler.get_running_count(), 3);
+
+        // Shrinking lets running tasks finish, then holds concurrency at the new limit
+        scheduler.set_max_concurrent_tasks(1);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(scheduler.get_running_count(), 1);
+        peak.store(0, AtomicOrdering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        handle.abort();
+
+        assert_eq!(peak.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_growing_cancels_pending_slot_retirement() {
+        tokio::time::pause();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: in_flight.clone(),
+            peak: peak.clone(),
+        });
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(idle_gpu_manager()),
+            Arc::new(loader_for(executor)),
+            Arc::new(MetricsCollector::new()),
+            3,
+        ));
+        for i in 0..10 {
+            scheduler.submit_task(make_task(&format!("task{}", i), 1, Duration::from_secs(60))).await.unwrap();
+        }
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(scheduler.get_running_count(), 3);
+
+        // Shrink by two while all slots are busy, then grow back by one: only
+        // one slot is still to be retired, and none is added
+        scheduler.set_max_concurrent_tasks(1);
+        scheduler.set_max_concurrent_tasks(2);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(scheduler.get_running_count(), 3);
+
+        // Once the first batch finishes, concurrency settles at the new limit
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        peak.store(0, AtomicOrdering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        handle.abort();
+        assert_eq!(peak.load(AtomicOrdering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_task_aborted_after_max_duration() {
+        tokio::time::pause();
+        let mut gpu_manager = MockGpuManager::new();
+        gpu_manager
+            .expect_acquire_gpu()
+            .times(1)
+            .returning(|| Ok("gpu1".to_string()));
+        gpu_manager
+            .expect_release_gpu()
+            .with(eq("gpu1".to_string()))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: Arc::new(AtomicUsize::new(0)),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        );
+
+        let task = make_task("slow", 1, Duration::from_millis(50));
+
+        let start = Instant::now();
+        let result = scheduler.process_task(&task, &Notify::new()).await;
+        assert!(matches!(result, Err(OmniTensorError::TaskTimeout(id)) if id == "slow"));
+        assert!(start.elapsed() < Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_and_running_tasks() {
+        tokio::time::pause();
+        let mut gpu_manager = MockGpuManager::new();
+        gpu_manager
+            .expect_acquire_gpu()
+            .times(1)
+            .returning(|| Ok("gpu1".to_string()));
+        gpu_manager
+            .expect_release_gpu()
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: Arc::new(AtomicUsize::new(0)),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        for (id, priority) in [("running", 9), ("queued", 1)] {
+            let task = make_task(id, priority, Duration::from_secs(60));
+            scheduler.submit_task(task).await.unwrap();
+        }
+
+        assert!(scheduler.cancel_task("queued").await.unwrap());
+        assert_eq!(scheduler.get_queue_length().await, 1);
+
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(scheduler.get_running_count(), 1);
+
+        assert!(scheduler.cancel_task("running").await.unwrap());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.abort();
+
+        assert_eq!(scheduler.get_running_count(), 0);
+        assert!(!scheduler.cancel_task("running").await.unwrap());
+        assert!(!scheduler.cancel_task("unknown").await.unwrap());
+    }
+
+    struct FlakyExecutor {
+        failures_left: AtomicUsize,
+        calls: Arc<AtomicUsize>,
+        successes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for FlakyExecutor {
+        async fn execute(&self, task: ComputeTask) -> Result<TaskResult, OmniTensorError> {
+            self.calls.fetch_add(1, AtomicOrdering::SeqCst);
+            let failing = self.failures_left
+                .fetch_update(AtomicOrdering::SeqCst, AtomicOrdering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                return Err(OmniTensorError::ExecutionFailed("transient GPU OOM".to_string()));
+            }
+            self.successes.fetch_add(1, AtomicOrdering::SeqCst);
+            Ok(TaskResult {
+                task_id: task.id,
+                output: vec![],
+                execution_time: Duration::from_millis(0),
+            })
+        }
+    }
+
+    async fn run_flaky_task(failures: usize, max_retries: u32) -> (usize, usize) {
+        tokio::time::pause();
+        let gpu_manager = idle_gpu_manager();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let successes = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(FlakyExecutor {
+            failures_left: AtomicUsize::new(failures),
+            calls: calls.clone(),
+            successes: successes.clone(),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        let mut task = make_task("flaky", 1, Duration::from_secs(60));
+        task.retry_policy = RetryPolicy {
+            max_retries,
+            base_backoff: Duration::from_millis(10),
+        };
+        scheduler.submit_task(task).await.unwrap();
+
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        handle.abort();
+
+        (calls.load(AtomicOrdering::SeqCst), successes.load(AtomicOrdering::SeqCst))
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_backoff: Duration::from_millis(100),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_task_retried_until_success() {
+        let (calls, successes) = run_flaky_task(2, 3).await;
+        assert_eq!(calls, 3);
+        assert_eq!(successes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_task_gives_up_after_max_retries() {
+        let (calls, successes) = run_flaky_task(usize::MAX, 2).await;
+        assert_eq!(calls, 3);
+        assert_eq!(successes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_retry_backoff() {
+        tokio::time::pause();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(FlakyExecutor {
+            failures_left: AtomicUsize::new(usize::MAX),
+            calls: calls.clone(),
+            successes: Arc::new(AtomicUsize::new(0)),
+        });
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(idle_gpu_manager()),
+            Arc::new(loader_for(executor)),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        let mut task = make_task("flaky", 1, Duration::from_secs(60));
+        task.retry_policy = RetryPolicy { max_retries: 3, base_backoff: Duration::from_secs(10) };
+        scheduler.submit_task(task).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+
+        // Wait for the first attempt to fail and start backing off
+        while !scheduler.retrying.lock().unwrap().contains_key("flaky") {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(scheduler.task_state("flaky"), Some(TaskState::Queued));
+        assert!(scheduler.cancel_task("flaky").await.unwrap());
+        assert_eq!(scheduler.task_state("flaky"), Some(TaskState::Cancelled));
+
+        // The backoff ends without the task being requeued
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        handle.abort();
+        assert_eq!(calls.load(AtomicOrdering::SeqCst), 1);
+        assert_eq!(scheduler.get_queue_length().await, 0);
+        assert_eq!(scheduler.task_state("flaky"), Some(TaskState::Cancelled));
+        assert!(!scheduler.cancel_task("flaky").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_task_failed_when_requeue_refused() {
+        tokio::time::pause();
+        let executor: Arc<dyn TaskExecutor> = Arc::new(FlakyExecutor {
+            failures_left: AtomicUsize::new(usize::MAX),
+            calls: Arc::new(AtomicUsize::new(0)),
+            successes: Arc::new(AtomicUsize::new(0)),
+        });
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(idle_gpu_manager()),
+            Arc::new(loader_for(executor)),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        let mut task = make_task("flaky", 1, Duration::from_secs(60));
+        task.retry_policy = RetryPolicy { max_retries: 3, base_backoff: Duration::from_secs(10) };
+        scheduler.submit_task(task).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+
+        while !scheduler.retrying.lock().unwrap().contains_key("flaky") {
+            tokio::task::yield_now().await;
+        }
+        // Shutting down during the backoff makes the requeue fail
+        scheduler.drain(Duration::ZERO).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        handle.abort();
+        assert!(matches!(scheduler.task_state("flaky"), Some(TaskState::Failed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_dispatched_task_cancellable_before_it_starts() {
+        let scheduler = TaskScheduler::new(
+            Arc::new(MockGpuManager::new()),
+            Arc::new(MockModelLoader::new()),
+            Arc::new(MetricsCollector::new()),
+            1,
+        );
+        scheduler.submit_task(make_task("task1", 1, Duration::from_secs(60))).await.unwrap();
+
+        // Popped but not yet executing: cancelling still reaches it
+        let (task, cancel) = scheduler.next_task().unwrap();
+        assert!(scheduler.cancel_task(&task.id).await.unwrap());
+        tokio::time::timeout(Duration::from_secs(1), cancel.notified()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tasks_dequeued_by_priority() {
+        let scheduler = TaskScheduler::new(
+            Arc::new(MockGpuManager::new()),
+            Arc::new(MockModelLoader::new()),
+            Arc::new(MetricsCollector::new()),
+            4,
+        );
+
+        for (id, priority) in [("low", 1), ("high", 9), ("mid", 5), ("high2", 9)] {
+            let task = make_task(id, priority, Duration::from_secs(60));
+            scheduler.submit_task(task).await.unwrap();
+        }
+        assert_eq!(scheduler.get_queue_length().await, 4);
+
+        let order: Vec<String> = std::iter::from_fn(|| scheduler.next_task())
+            .map(|(task, _)| task.id)
+            .collect();
+        assert_eq!(order, vec!["high", "high2", "mid", "low"]);
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running_tasks() {
+        tokio::time::pause();
+        let mut gpu_manager = MockGpuManager::new();
+        gpu_manager
+            .expect_acquire_gpu()
+            .times(1)
+            .returning(|| Ok("gpu1".to_string()));
+        gpu_manager
+            .expect_release_gpu()
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: in_flight.clone(),
+            peak: Arc::new(AtomicUsize::new(0)),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        scheduler.submit_task(make_task("running", 1, Duration::from_secs(60))).await.unwrap();
+        scheduler.submit_task(make_task("queued", 1, Duration::from_secs(60))).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(in_flight.load(AtomicOrdering::SeqCst), 1);
+
+        assert!(scheduler.drain(Duration::from_secs(5)).await);
+        assert_eq!(scheduler.get_running_count(), 0);
+
+        // New work is refused, and the task that never started is left undispatched
+        let refused = scheduler.submit_task(make_task("late", 1, Duration::from_secs(60))).await;
+        assert!(matches!(refused, Err(OmniTensorError::ShuttingDown)));
+        assert_eq!(scheduler.get_queue_length().await, 1);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(handle.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_drain_times_out() {
+        tokio::time::pause();
+        let gpu_manager = idle_gpu_manager();
+
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: Arc::new(AtomicUsize::new(0)),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        scheduler.submit_task(make_task("slow", 1, Duration::from_secs(60))).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(!scheduler.drain(Duration::from_millis(20)).await);
+        assert_eq!(scheduler.get_running_count(), 1);
+        handle.abort();
+    }
+
+    struct CountingExecutor {
+        executions: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for CountingExecutor {
+        async fn execute(&self, task: ComputeTask) -> Result<TaskResult, OmniTensorError> {
+            self.executions.fetch_add(1, AtomicOrdering::SeqCst);
+            Ok(TaskResult {
+                task_id: task.id,
+                output: task.input_data.iter().rev().copied().collect(),
+                execution_time: Duration::from_millis(1),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_task_served_from_cache() {
+        let mut gpu_manager = MockGpuManager::new();
+        gpu_manager
+            .expect_acquire_gpu()
+            .times(2)
+            .returning(|| Ok("gpu1".to_string()));
+        gpu_manager
+            .expect_release_gpu()
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let executions = Arc::new(AtomicUsize::new(0));
+        let executor: Arc<dyn TaskExecutor> = Arc::new(CountingExecutor { executions: executions.clone() });
+        let mut model_loader = MockModelLoader::new();
+        model_loader
+            .expect_load_model()
+            .times(2)
+            .returning(move |_| Ok(executor.clone()));
+
+        let metrics = Arc::new(MetricsCollector::new());
+        let scheduler = TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            metrics.clone(),
+            1,
+        ).with_result_cache(ResultCacheConfig::default());
+
+        let task = |id: &str, input: Vec<u8>| ComputeTask { input_data: input, ..make_task(id, 1, Duration::from_secs(60)) };
+        let first = scheduler.process_task(&task("first", vec![1, 2, 3]), &Notify::new()).await.unwrap();
+        let repeat = scheduler.process_task(&task("repeat", vec![1, 2, 3]), &Notify::new()).await.unwrap();
+        assert_eq!(executions.load(AtomicOrdering::SeqCst), 1);
+        assert_eq!(metrics.task_cache_hits(), 1);
+        assert_eq!(repeat.task_id, "repeat");
+        assert_eq!(repeat.output, first.output);
+
+        // A different input runs the model again
+        scheduler.process_task(&task("other", vec![4, 5]), &Notify::new()).await.unwrap();
+        assert_eq!(executions.load(AtomicOrdering::SeqCst), 2);
+        assert_eq!(metrics.task_cache_hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_completion_posts_webhook() {
+        let gpu_manager = idle_gpu_manager();
+
+        let executor: Arc<dyn TaskExecutor> = Arc::new(CountingExecutor { executions: Arc::new(AtomicUsize::new(0)) });
+        let model_loader = loader_for(executor);
+
+        let key_dir = tempfile::tempdir().unwrap();
+        let identity = Arc::new(crate::identity::NodeIdentity::load_or_generate(key_dir.path()).unwrap());
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ).with_webhooks(Arc::new(WebhookNotifier::new(identity).with_internal_hosts_allowed(true))));
+
+        let (url, mut posts) = crate::webhook::test_server::capture_posts(vec![]).await;
+        let task = ComputeTask {
+            input_data: vec![1, 2, 3],
+            callback_url: Some(url),
+            ..make_task("task1", 1, Duration::from_secs(60))
+        };
+        scheduler.submit_task(task).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+
+        let post = tokio::time::timeout(Duration::from_secs(5), posts.recv()).await.unwrap().unwrap();
+        handle.abort();
+        let body: serde_json::Value = serde_json::from_slice(&post.body).unwrap();
+        assert_eq!(body, serde_json::json!({
+            "task_id": "task1",
+            "status": "completed",
+            "result_hash": result_hash(&[3, 2, 1]),
+            "error": null,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_posts_cancelled_webhook() {
+        let key_dir = tempfile::tempdir().unwrap();
+        let identity = Arc::new(crate::identity::NodeIdentity::load_or_generate(key_dir.path()).unwrap());
+        let scheduler = TaskScheduler::new(
+            Arc::new(MockGpuManager::new()),
+            Arc::new(MockModelLoader::new()),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ).with_webhooks(Arc::new(WebhookNotifier::new(identity).with_internal_hosts_allowed(true)));
+
+        let (url, mut posts) = crate::webhook::test_server::capture_posts(vec![]).await;
+        let task = ComputeTask {
+            callback_url: Some(url),
+            ..make_task("task1", 1, Duration::from_secs(60))
+        };
+        scheduler.submit_task(task).await.unwrap();
+        assert!(scheduler.cancel_task("task1").await.unwrap());
+
+        let post = tokio::time::timeout(Duration::from_secs(5), posts.recv()).await.unwrap().unwrap();
+        let notification: TaskNotification = serde_json::from_slice(&post.body).unwrap();
+        assert_eq!(notification, TaskNotification::cancelled("task1"));
+    }
+
+    #[tokio::test]
+    async fn test_result_cache_bounds() {
+        tokio::time::pause();
+        let result = |id: &str| TaskResult { task_id: id.to_string(), output: vec![7], execution_time: Duration::ZERO };
+        let task = |id: &str, model_id: &str, input: u8| ComputeTask {
+            model_id: model_id.to_string(),
+            input_data: vec![input],
+            ..make_task(id, 1, Duration::from_secs(60))
+        };
+
+        let cache = ResultCache::new(ResultCacheConfig {
+            max_entries: 2,
+            ttl: Duration::from_millis(100),
+            uncached_models: HashSet::from(["sampler".to_string()]),
+        });
+        for (id, input) in [("a", 1), ("b", 2), ("c", 3)] {
+            cache.insert(&task(id, "model1", input), &result(id));
+            tokio::time::advance(Duration::from_millis(1)).await;
+        }
+        assert_eq!(cache.len(), 2);
+        // The oldest entry was evicted to make room
+        assert!(cache.get(&task("a2", "model1", 1)).is_none());
+        assert!(cache.get(&task("c2", "model1", 3)).is_some());
+        // Same input under another model is a different entry
+        assert!(cache.get(&task("c3", "model2", 3)).is_none());
+
+        cache.insert(&task("s", "sampler", 1), &result("s"));
+        assert!(cache.get(&task("s2", "sampler", 1)).is_none());
+
+        tokio::time::advance(Duration::from_millis(150)).await;
+        assert!(cache.get(&task("c4", "model1", 3)).is_none());
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_execution_logs_carry_task_span() {
+        tokio::time::pause();
+        let gpu_manager = idle_gpu_manager();
+
+        let executor: Arc<dyn TaskExecutor> = Arc::new(SlowExecutor {
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: Arc::new(AtomicUsize::new(0)),
+        });
+        let model_loader = loader_for(executor);
+
+        let scheduler = Arc::new(TaskScheduler::new(
+            Arc::new(gpu_manager),
+            Arc::new(model_loader),
+            Arc::new(MetricsCollector::new()),
+            1,
+        ));
+
+        scheduler.submit_task(make_task("traced", 1, Duration::from_secs(60))).await.unwrap();
+        let handle = tokio::spawn(Arc::clone(&scheduler).run());
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        handle.abort();
+
+        logs_assert(|lines: &[&str]| {
+            let completed = lines.iter()
+                .find(|line| line.contains("Task traced completed"))
+                .ok_or_else(|| "no completion log".to_string())?;
+            if completed.contains("task_id=traced") && completed.contains("trace_id=trace-traced") {
+                Ok(())
+            } else {
+                Err(format!("completion log is missing the task span: {}", completed))
+            }
+        });
+    }
+}
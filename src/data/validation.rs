@@ -1,8 +1,12 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use async_trait::async_trait;
+use regex::Regex;
+use sha2::{Digest, Sha256};
 
 use crate::models::DataItem;
 use crate::storage::DataStore;
@@ -16,6 +20,8 @@ pub enum ValidationError {
     ConsensusFailure,
     #[error("Database error: {0}")]
     DatabaseError(String),
+    #[error("Invalid validation config: {0}")]
+    InvalidConfig(String),
     #[error("Unknown error occurred")]
     Unknown,
 }
@@ -23,24 +29,154 @@ pub enum ValidationError {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub is_valid: bool,
+    /// Share of the total vote weight that agreed with the outcome.
     pub confidence: f64,
     pub validator_count: usize,
 }
 
+/// A single validator's verdict on a data item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorVote {
+    pub validator_id: String,
+    pub is_valid: bool,
+}
+
+/// Voting weight per validator id, e.g. derived from stake or reputation.
+/// Validators without an entry count with weight 1.0.
+pub type ValidatorWeights = HashMap<String, f64>;
+
+/// Limits applied by `DataValidator` before an item is sent for consensus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    pub max_text_len: usize,
+    /// Regex that text items must match, in addition to the length limit.
+    pub text_pattern: Option<String>,
+    pub max_image_bytes: usize,
+    pub min_numeric: f64,
+    pub max_numeric: f64,
+    pub max_audio_bytes: usize,
+    pub max_video_bytes: usize,
+    /// How long a validation result is reused for identical data; 0 disables caching.
+    pub result_cache_ttl_secs: u64,
+    /// Most results kept at once; the oldest is dropped to make room. 0 disables caching.
+    pub result_cache_max_entries: usize,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            max_text_len: 1000,
+            text_pattern: None,
+            max_image_bytes: 10_000_000, // 10MB
+            min_numeric: 0.0,
+            max_numeric: 1.0,
+            max_audio_bytes: 50_000_000, // 50MB
+            max_video_bytes: 200_000_000, // 200MB
+            result_cache_ttl_secs: 300,
+            result_cache_max_entries: 10_000,
+        }
+    }
+}
+
 pub struct DataValidator {
     data_store: Arc<Mutex<dyn DataStore>>,
     consensus_manager: Arc<ConsensusManager>,
+    config: ValidationConfig,
+    text_pattern: Option<Regex>,
+    validator_weights: RwLock<ValidatorWeights>,
+    /// Results of earlier validations keyed by the SHA-256 of the item's content.
+    result_cache: RwLock<HashMap<[u8; 32], (Instant, ValidationResult)>>,
 }
 
 impl DataValidator {
-    pub fn new(data_store: Arc<Mutex<dyn DataStore>>, consensus_manager: Arc<ConsensusManager>) -> Self {
-        Self {
+    pub fn new(
+        data_store: Arc<Mutex<dyn DataStore>>,
+        consensus_manager: Arc<ConsensusManager>,
+        config: ValidationConfig,
+    ) -> Result<Self, ValidationError> {
+        let text_pattern = Self::compile_text_pattern(&config)?;
+
+        Ok(Self {
             data_store,
             consensus_manager,
+            config,
+            text_pattern,
+            validator_weights: RwLock::new(ValidatorWeights::new()),
+            result_cache: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn compile_text_pattern(config: &ValidationConfig) -> Result<Option<Regex>, ValidationError> {
+        config.text_pattern.as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| ValidationError::InvalidConfig(e.to_string()))
+    }
+
+    /// Replaces the validation rules. Cached results were produced under the old
+    /// rules, so they are dropped.
+    pub fn update_config(&mut self, config: ValidationConfig) -> Result<(), ValidationError> {
+        self.text_pattern = Self::compile_text_pattern(&config)?;
+        if config != self.config {
+            self.clear_result_cache();
         }
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn set_validator_weights(&self, weights: ValidatorWeights) {
+        if let Ok(mut validator_weights) = self.validator_weights.write() {
+            *validator_weights = weights;
+        }
+        // Cached outcomes were tallied with the old weights
+        self.clear_result_cache();
+    }
+
+    fn clear_result_cache(&self) {
+        if let Ok(mut cache) = self.result_cache.write() {
+            cache.clear();
+        }
+    }
+
+    fn cached_result(&self, key: &[u8; 32]) -> Option<ValidationResult> {
+        let ttl = Duration::from_secs(self.config.result_cache_ttl_secs);
+        let cache = self.result_cache.read().ok()?;
+        let (stored_at, result) = cache.get(key)?;
+        (stored_at.elapsed() < ttl).then(|| result.clone())
+    }
+
+    fn cache_result(&self, key: [u8; 32], result: &ValidationResult) {
+        let ttl = Duration::from_secs(self.config.result_cache_ttl_secs);
+        let max_entries = self.config.result_cache_max_entries;
+        if ttl.is_zero() || max_entries == 0 {
+            return;
+        }
+        if let Ok(mut cache) = self.result_cache.write() {
+            cache.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+            while cache.len() >= max_entries && !cache.contains_key(&key) {
+                let oldest = cache.iter()
+                    .min_by_key(|(_, (stored_at, _))| *stored_at)
+                    .map(|(key, _)| *key);
+                match oldest {
+                    Some(oldest) => cache.remove(&oldest),
+                    None => break,
+                };
+            }
+            cache.insert(key, (Instant::now(), result.clone()));
+        }
+    }
+
+    fn validator_weights(&self) -> ValidatorWeights {
+        self.validator_weights.read().map(|weights| weights.clone()).unwrap_or_default()
     }
 
     pub async fn validate_data(&self, data: DataItem) -> Result<ValidationResult, ValidationError> {
+        let cache_key = content_hash(&data);
+        if let Some(cached) = self.cached_result(&cache_key) {
+            return Ok(cached);
+        }
+
         if !self.is_valid_format(&data) {
             return Err(ValidationError::InvalidFormat);
         }
@@ -48,28 +184,89 @@ impl DataValidator {
         let validation_result = self.reach_consensus(&data).await?;
 
         self.store_validation_result(&data, &validation_result).await?;
+        self.cache_result(cache_key, &validation_result);
 
         Ok(validation_result)
     }
 
+    /// Validates several items with a single consensus round. Items are format-checked
+    /// locally first, and only the valid ones are sent for consensus. Results are
+    /// returned in the same order as `items`.
+    pub async fn validate_batch(&self, items: Vec<DataItem>) -> Vec<Result<ValidationResult, ValidationError>> {
+        let mut results: Vec<Option<Result<ValidationResult, ValidationError>>> = Vec::with_capacity(items.len());
+        let mut to_validate = Vec::new();
+        let mut positions = Vec::new();
+        for (position, item) in items.into_iter().enumerate() {
+            if let Some(cached) = self.cached_result(&content_hash(&item)) {
+                results.push(Some(Ok(cached)));
+            } else if self.is_valid_format(&item) {
+                positions.push(position);
+                to_validate.push(item);
+                results.push(None);
+            } else {
+                results.push(Some(Err(ValidationError::InvalidFormat)));
+            }
+        }
+
+        if !to_validate.is_empty() {
+            match self.reach_consensus_batch(&to_validate).await {
+                Ok(validation_results) => {
+                    for ((position, data), validation_result) in positions.into_iter().zip(&to_validate).zip(validation_results) {
+                        let stored = self.store_validation_result(data, &validation_result).await;
+                        if stored.is_ok() {
+                            self.cache_result(content_hash(data), &validation_result);
+                        }
+                        results[position] = Some(stored.map(|_| validation_result));
+                    }
+                },
+                Err(_) => {
+                    for position in positions {
+                        results[position] = Some(Err(ValidationError::ConsensusFailure));
+                    }
+                },
+            }
+        }
+
+        results.into_iter()
+            .map(|result| result.unwrap_or(Err(ValidationError::Unknown)))
+            .collect()
+    }
+
     fn is_valid_format(&self, data: &DataItem) -> bool {
-        // Implement format validation logic
+        let config = &self.config;
         match data {
-            DataItem::Text(text) => !text.is_empty() && text.len() <= 1000,
-            DataItem::Image(image_data) => image_data.len() > 0 && image_data.len() <= 10_000_000, // Max 10MB
-            DataItem::Numeric(num) => *num >= 0.0 && *num <= 1.0,
+            DataItem::Text(text) => {
+                !text.is_empty() && text.len() <= config.max_text_len
+                    && self.text_pattern.as_ref().map_or(true, |pattern| pattern.is_match(text))
+            },
+            DataItem::Image(image_data) => image_data.len() > 0 && image_data.len() <= config.max_image_bytes,
+            DataItem::Numeric(num) => *num >= config.min_numeric && *num <= config.max_numeric,
+            DataItem::Audio(audio_data) => audio_data.len() <= config.max_audio_bytes && is_wav(audio_data),
+            DataItem::Video(video_data) => video_data.len() <= config.max_video_bytes && is_mp4(video_data),
         }
     }
 
     async fn reach_consensus(&self, data: &DataItem) -> Result<ValidationResult, ValidationError> {
-        let consensus_result = self.consensus_manager.reach_consensus(data).await
+        let weights = self.validator_weights();
+        let votes = self.consensus_manager.reach_consensus(data, &weights).await
             .map_err(|_| ValidationError::ConsensusFailure)?;
 
-        Ok(ValidationResult {
-            is_valid: consensus_result.is_valid,
-            confidence: consensus_result.confidence,
-            validator_count: consensus_result.validator_count,
-        })
+        tally_votes(&votes, &weights)
+    }
+
+    async fn reach_consensus_batch(&self, items: &[DataItem]) -> Result<Vec<ValidationResult>, ValidationError> {
+        let weights = self.validator_weights();
+        let votes_per_item = self.consensus_manager.reach_consensus_batch(items, &weights).await
+            .map_err(|_| ValidationError::ConsensusFailure)?;
+
+        // One result per item is required to line results back up with the batch
+        if votes_per_item.len() != items.len() {
+            return Err(ValidationError::ConsensusFailure);
+        }
+
+        votes_per_item.iter()
+            .map(|votes| tally_votes(votes, &weights))
+            .collect()
     }
 
     async fn store_validation_result(&self, data: &DataItem, result: &ValidationResult) -> Result<(), ValidationError> {
@@ -79,6 +276,69 @@ impl DataValidator {
     }
 }
 
+/// Decides the outcome by weighted majority; `confidence` is the winning side's
+/// share of the total weight. Fails if no validator carried any weight.
+fn tally_votes(votes: &[ValidatorVote], weights: &ValidatorWeights) -> Result<ValidationResult, ValidationError> {
+    let (mut valid_weight, mut total_weight) = (0.0, 0.0);
+    for vote in votes {
+        let weight = weights.get(&vote.validator_id).copied().unwrap_or(1.0).max(0.0);
+        total_weight += weight;
+        if vote.is_valid {
+            valid_weight += weight;
+        }
+    }
+
+    if total_weight <= 0.0 {
+        return Err(ValidationError::ConsensusFailure);
+    }
+
+    let is_valid = valid_weight * 2.0 > total_weight;
+    let agreeing_weight = if is_valid { valid_weight } else { total_weight - valid_weight };
+    Ok(ValidationResult {
+        is_valid,
+        confidence: agreeing_weight / total_weight,
+        validator_count: votes.len(),
+    })
+}
+
+/// SHA-256 over the item's variant and content, so identical submissions share a key.
+fn content_hash(data: &DataItem) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    match data {
+        DataItem::Text(text) => {
+            hasher.update(b"text");
+            hasher.update(text.as_bytes());
+        },
+        DataItem::Image(image_data) => {
+            hasher.update(b"image");
+            hasher.update(image_data);
+        },
+        DataItem::Numeric(num) => {
+            hasher.update(b"numeric");
+            hasher.update(num.to_bits().to_le_bytes());
+        },
+        DataItem::Audio(audio_data) => {
+            hasher.update(b"audio");
+            hasher.update(audio_data);
+        },
+        DataItem::Video(video_data) => {
+            hasher.update(b"video");
+            hasher.update(video_data);
+        },
+    }
+    hasher.finalize().into()
+}
+
+/// RIFF container with a WAVE form type.
+fn is_wav(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE"
+}
+
+/// ISO base media file whose first box is `ftyp`.
+fn is_mp4(data: &[u8]) -> bool {
+    data.len() >= 8 && &data[4..8] == b"ftyp"
+}
+
 #[async_trait]
 pub trait DataStore: Send + Sync {
     async fn store_validation_result(&mut self, id: String, result: &ValidationResult) -> Result<(), String>;
@@ -102,10 +362,18 @@ mod tests {
         ConsensusManager {}
         impl ConsensusManager {
             fn new() -> Self;
-            async fn reach_consensus(&self, data: &DataItem) -> Result<ValidationResult, ValidationError>;
+            async fn reach_consensus(&self, data: &DataItem, weights: &ValidatorWeights) -> Result<Vec<ValidatorVote>, ValidationError>;
+            async fn reach_consensus_batch(&self, items: &[DataItem], weights: &ValidatorWeights) -> Result<Vec<Vec<ValidatorVote>>, ValidationError>;
         }
     }
 
+    /// `valid` approving votes followed by `invalid` rejecting ones, from distinct validators.
+    fn votes(valid: usize, invalid: usize) -> Vec<ValidatorVote> {
+        (0..valid + invalid)
+            .map(|i| ValidatorVote { validator_id: format!("validator-{}", i), is_valid: i < valid })
+            .collect()
+    }
+
     #[tokio::test]
     async fn test_validate_data_success() {
         let mut mock_store = MockDataStore::new();
@@ -117,16 +385,13 @@ mod tests {
         let mut mock_consensus = MockConsensusManager::new();
         mock_consensus
             .expect_reach_consensus()
-            .returning(|_| Ok(ValidationResult {
-                is_valid: true,
-                confidence: 0.95,
-                validator_count: 10,
-            }));
+            .returning(|_, _| Ok(votes(10, 0)));
 
         let validator = DataValidator::new(
             Arc::new(Mutex::new(mock_store)),
             Arc::new(mock_consensus),
-        );
+            ValidationConfig::default(),
+        ).unwrap();
 
         let result = validator.validate_data(DataItem::Text("Valid data".to_string())).await;
         assert!(result.is_ok());
@@ -141,7 +406,8 @@ mod tests {
         let validator = DataValidator::new(
             Arc::new(Mutex::new(mock_store)),
             Arc::new(mock_consensus),
-        );
+            ValidationConfig::default(),
+        ).unwrap();
 
         let result = validator.validate_data(DataItem::Text("".to_string())).await;
         assert!(matches!(result, Err(ValidationError::InvalidFormat)));
@@ -154,14 +420,226 @@ mod tests {
         let mut mock_consensus = MockConsensusManager::new();
         mock_consensus
             .expect_reach_consensus()
-            .returning(|_| Err(ValidationError::ConsensusFailure));
+            .returning(|_, _| Err(ValidationError::ConsensusFailure));
 
         let validator = DataValidator::new(
             Arc::new(Mutex::new(mock_store)),
             Arc::new(mock_consensus),
-        );
+            ValidationConfig::default(),
+        ).unwrap();
 
         let result = validator.validate_data(DataItem::Text("Valid data".to_string())).await;
         assert!(matches!(result, Err(ValidationError::ConsensusFailure)));
     }
-}
\ No newline at end of file
+
+    fn validator_with(config: ValidationConfig) -> DataValidator {
+        DataValidator::new(
+            Arc::new(Mutex::new(MockDataStore::new())),
+            Arc::new(MockConsensusManager::new()),
+            config,
+        ).unwrap()
+    }
+
+    fn wav_bytes(len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[0..4].copy_from_slice(b"RIFF");
+        data[8..12].copy_from_slice(b"WAVE");
+        data
+    }
+
+    fn mp4_bytes(len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[4..8].copy_from_slice(b"ftyp");
+        data
+    }
+
+    #[test]
+    fn test_audio_and_video_formats() {
+        let validator = validator_with(ValidationConfig::default());
+
+        assert!(validator.is_valid_format(&DataItem::Audio(wav_bytes(1024))));
+        assert!(validator.is_valid_format(&DataItem::Video(mp4_bytes(1024))));
+
+        // Wrong container
+        assert!(!validator.is_valid_format(&DataItem::Audio(mp4_bytes(1024))));
+        assert!(!validator.is_valid_format(&DataItem::Video(wav_bytes(1024))));
+        assert!(!validator.is_valid_format(&DataItem::Audio(Vec::new())));
+
+        // Oversized
+        assert!(!validator.is_valid_format(&DataItem::Audio(wav_bytes(ValidationConfig::default().max_audio_bytes + 1))));
+        assert!(!validator.is_valid_format(&DataItem::Video(mp4_bytes(ValidationConfig::default().max_video_bytes + 1))));
+    }
+
+    #[tokio::test]
+    async fn test_validate_oversized_video() {
+        let validator = validator_with(ValidationConfig::default());
+
+        let result = validator.validate_data(DataItem::Video(mp4_bytes(ValidationConfig::default().max_video_bytes + 1))).await;
+        assert!(matches!(result, Err(ValidationError::InvalidFormat)));
+    }
+
+    #[test]
+    fn test_custom_validation_config() {
+        let defaults = validator_with(ValidationConfig::default());
+        let custom = validator_with(ValidationConfig {
+            max_text_len: 2000,
+            text_pattern: Some("^[a-z ]+$".to_string()),
+            min_numeric: -1.0,
+            max_numeric: 0.5,
+            ..ValidationConfig::default()
+        });
+
+        // Accepted only by the custom config
+        let long_text = DataItem::Text("a".repeat(1500));
+        assert!(!defaults.is_valid_format(&long_text));
+        assert!(custom.is_valid_format(&long_text));
+        assert!(!defaults.is_valid_format(&DataItem::Numeric(-0.5)));
+        assert!(custom.is_valid_format(&DataItem::Numeric(-0.5)));
+
+        // Accepted only by the defaults
+        let mixed_case = DataItem::Text("Valid data".to_string());
+        assert!(defaults.is_valid_format(&mixed_case));
+        assert!(!custom.is_valid_format(&mixed_case));
+        assert!(defaults.is_valid_format(&DataItem::Numeric(0.75)));
+        assert!(!custom.is_valid_format(&DataItem::Numeric(0.75)));
+    }
+
+    #[test]
+    fn test_invalid_text_pattern_rejected() {
+        let result = DataValidator::new(
+            Arc::new(Mutex::new(MockDataStore::new())),
+            Arc::new(MockConsensusManager::new()),
+            ValidationConfig { text_pattern: Some("(".to_string()), ..ValidationConfig::default() },
+        );
+        assert!(matches!(result, Err(ValidationError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validate_batch_mixed() {
+        let mut mock_store = MockDataStore::new();
+        mock_store
+            .expect_store_validation_result()
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let mut mock_consensus = MockConsensusManager::new();
+        mock_consensus.expect_reach_consensus().never();
+        mock_consensus
+            .expect_reach_consensus_batch()
+            .times(1)
+            .withf(|items: &[DataItem], _| items.len() == 2)
+            .returning(|_, _| Ok(vec![votes(9, 1), votes(2, 8)]));
+
+        let validator = DataValidator::new(
+            Arc::new(Mutex::new(mock_store)),
+            Arc::new(mock_consensus),
+            ValidationConfig::default(),
+        ).unwrap();
+
+        let results = validator.validate_batch(vec![
+            DataItem::Text("Valid data".to_string()),
+            DataItem::Text("".to_string()),
+            DataItem::Numeric(0.5),
+            DataItem::Numeric(2.0),
+        ]).await;
+
+        assert_eq!(results.len(), 4);
+        assert!(matches!(&results[0], Ok(result) if result.is_valid));
+        assert!(matches!(results[1], Err(ValidationError::InvalidFormat)));
+        assert!(matches!(&results[2], Ok(result) if !result.is_valid));
+        assert!(matches!(results[3], Err(ValidationError::InvalidFormat)));
+    }
+
+    #[tokio::test]
+    async fn test_high_weight_minority_outvotes_majority() {
+        let mut mock_store = MockDataStore::new();
+        mock_store
+            .expect_store_validation_result()
+            .returning(|_, _| Ok(()));
+
+        // Five low-weight validators approve, two high-weight validators reject
+        let mut mock_consensus = MockConsensusManager::new();
+        mock_consensus
+            .expect_reach_consensus()
+            .withf(|_, weights: &ValidatorWeights| weights.len() == 2)
+            .returning(|_, _| Ok(votes(5, 2)));
+
+        let validator = DataValidator::new(
+            Arc::new(Mutex::new(mock_store)),
+            Arc::new(mock_consensus),
+            ValidationConfig::default(),
+        ).unwrap();
+        validator.set_validator_weights(HashMap::from([
+            ("validator-5".to_string(), 10.0),
+            ("validator-6".to_string(), 10.0),
+        ]));
+
+        let result = validator.validate_data(DataItem::Text("Valid data".to_string())).await.unwrap();
+        assert!(!result.is_valid);
+        assert_eq!(result.validator_count, 7);
+        assert!((result.confidence - 20.0 / 25.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_validation_served_from_cache() {
+        let mut mock_store = MockDataStore::new();
+        mock_store
+            .expect_store_validation_result()
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let mut mock_consensus = MockConsensusManager::new();
+        mock_consensus
+            .expect_reach_consensus()
+            .times(2)
+            .returning(|_, _| Ok(votes(8, 2)));
+
+        let mut validator = DataValidator::new(
+            Arc::new(Mutex::new(mock_store)),
+            Arc::new(mock_consensus),
+            ValidationConfig::default(),
+        ).unwrap();
+
+        let first = validator.validate_data(DataItem::Text("Valid data".to_string())).await.unwrap();
+        // Served from the cache without another consensus round
+        let second = validator.validate_data(DataItem::Text("Valid data".to_string())).await.unwrap();
+        assert_eq!(first.confidence, second.confidence);
+
+        // A config change drops the cache, so the next call reaches consensus again
+        validator.update_config(ValidationConfig { max_text_len: 500, ..ValidationConfig::default() }).unwrap();
+        validator.validate_data(DataItem::Text("Valid data".to_string())).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_result_cache_evicts_oldest_when_full() {
+        let mut mock_store = MockDataStore::new();
+        mock_store
+            .expect_store_validation_result()
+            .returning(|_, _| Ok(()));
+
+        // Three distinct items, then the evicted first one again
+        let mut mock_consensus = MockConsensusManager::new();
+        mock_consensus
+            .expect_reach_consensus()
+            .times(4)
+            .returning(|_, _| Ok(votes(8, 2)));
+
+        let validator = DataValidator::new(
+            Arc::new(Mutex::new(mock_store)),
+            Arc::new(mock_consensus),
+            ValidationConfig { result_cache_max_entries: 2, ..ValidationConfig::default() },
+        ).unwrap();
+
+        for value in [0.1, 0.2, 0.3] {
+            validator.validate_data(DataItem::Numeric(value)).await.unwrap();
+            // Keep insertion times distinct so the oldest entry is well defined
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(validator.result_cache.read().unwrap().len(), 2);
+
+        // The two newest are still cached; the oldest needs consensus again
+        validator.validate_data(DataItem::Numeric(0.3)).await.unwrap();
+        validator.validate_data(DataItem::Numeric(0.2)).await.unwrap();
+        validator.validate_data(DataItem::Numeric(0.1)).await.unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+/// Address the event feed listens on unless overridden on the command line.
+pub const DEFAULT_EVENTS_ADDR: &str = "127.0.0.1:3032";
+
+/// Events buffered per subscriber before it counts as too slow and is dropped.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Event published to dashboards, serialized as JSON with a `type` tag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    TaskReceived { task_id: String },
+    TaskCompleted { task_id: String, result_hash: String },
+    TaskFailed { task_id: String, error: String },
+    ModelUpdated { model_id: String, version: String },
+}
+
+impl NodeEvent {
+    /// The event's `type` tag, which subscribers filter on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NodeEvent::TaskReceived { .. } => "task_received",
+            NodeEvent::TaskCompleted { .. } => "task_completed",
+            NodeEvent::TaskFailed { .. } => "task_failed",
+            NodeEvent::ModelUpdated { .. } => "model_updated",
+        }
+    }
+}
+
+/// Fans events out from the main loop to any number of subscribers. Publishing
+/// never blocks; subscribers that fall behind lose their subscription.
+#[derive(Clone)]
+pub struct EventFeed {
+    sender: broadcast::Sender<NodeEvent>,
+}
+
+impl EventFeed {
+    pub fn new() -> Self {
+        Self { sender: broadcast::channel(SUBSCRIBER_BUFFER).0 }
+    }
+
+    pub fn publish(&self, event: NodeEvent) {
+        // Nobody may be subscribed
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// WebSocket endpoint streaming the event feed at `/events`. Clients can pass
+/// `?types=task_completed,peer_connected` to receive only those events.
+pub struct EventServer {
+    listener: TcpListener,
+    feed: EventFeed,
+}
+
+impl EventServer {
+    pub async fn bind(addr: &str, feed: EventFeed) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await
+            .with_context(|| format!("Failed to bind event feed to {}", addr))?;
+        Ok(Self { listener, feed })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serves subscribers until `shutdown` completes.
+    pub async fn serve(self, shutdown: impl Future<Output = ()>) {
+        let feed = self.feed;
+        serve_connections(self.listener, "Event feed", move |stream| handle_subscriber(stream, feed.subscribe()), shutdown).await
+    }
+}
+
+/// Accepts connections on `listener` until `shutdown` completes, handling each
+/// on its own task. `name` identifies the endpoint in logs.
+async fn serve_connections<H, F>(listener: TcpListener, name: &str, handle: H, shutdown: impl Future<Output = ()>)
+where
+    H: Fn(TcpStream) -> F,
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    if let Ok(addr) = listener.local_addr() {
+        info!("{} endpoint listening on {}", name, addr);
+    }
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let connection = handle(stream);
+                    let name = name.to_string();
+                    tokio::spawn(async move {
+                        if let Err(e) = connection.await {
+                            debug!("{} connection failed: {}", name, e);
+                        }
+                    });
+                },
+                Err(e) => debug!("Failed to accept {} connection: {}", name.to_lowercase(), e),
+            },
+            _ = &mut shutdown => break,
+        }
+    }
+    info!("{} endpoint stopped", name);
+}
+
+/// Event types requested in the query string; `None` means all of them.
+fn parse_filter(query: Option<&str>) -> Option<HashSet<String>> {
+    let types = query?.split('&').find_map(|pair| pair.strip_prefix("types="))?;
+    Some(types.split(',').filter(|t| !t.is_empty()).map(str::to_string).collect())
+}
+
+async fn handle_subscriber(stream: TcpStream, mut events: broadcast::Receiver<NodeEvent>) -> Result<()> {
+    let mut filter = None;
+    let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if request.uri().path() != "/events" {
+            let mut not_found = ErrorResponse::new(Some("not found".to_string()));
+            *not_found.status_mut() = tokio_tungstenite::tungstenite::http::StatusCode::NOT_FOUND;
+            return Err(not_found);
+        }
+        filter = parse_filter(request.uri().query());
+        Ok(response)
+    };
+    let socket = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+    let (mut sink, mut incoming) = socket.split();
+    debug!("Event subscriber connected");
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if filter.as_ref().map_or(true, |types| types.contains(event.kind())) {
+                        sink.send(Message::Text(serde_json::to_string(&event)?)).await?;
+                    }
+                },
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Dropping event subscriber that fell {} event(s) behind", missed);
+                    break;
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {},
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+
+    sink.close().await.ok();
+    info!("Event subscriber disconnected");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn start_server(feed: EventFeed) -> SocketAddr {
+        let server = EventServer::bind("127.0.0.1:0", feed).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.serve(std::future::pending()));
+        addr
+    }
+
+    async fn next_event<S>(socket: &mut S) -> NodeEvent
+    where
+        S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap().unwrap().unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_task_completed() {
+        let feed = EventFeed::new();
+        let addr = start_server(feed.clone()).await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/events", addr)).await.unwrap();
+        // Give the server a moment to subscribe before publishing
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let completed = NodeEvent::TaskCompleted { task_id: "task1".to_string(), result_hash: "ab12".to_string() };
+        feed.publish(completed.clone());
+        assert_eq!(next_event(&mut socket).await, completed);
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_filters_by_type() {
+        let feed = EventFeed::new();
+        let addr = start_server(feed.clone()).await;
+        let url = format!("ws://{}/events?types=model_updated,task_failed", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        feed.publish(NodeEvent::TaskReceived { task_id: "task1".to_string() });
+        let updated = NodeEvent::ModelUpdated { model_id: "model1".to_string(), version: "2".to_string() };
+        feed.publish(updated.clone());
+        assert_eq!(next_event(&mut socket).await, updated);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_is_dropped() {
+        let feed = EventFeed::new();
+        let addr = start_server(feed.clone()).await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/events", addr)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Publishing outruns delivery by far more than the buffer, and never blocks
+        for n in 0..(SUBSCRIBER_BUFFER * 20) {
+            feed.publish(NodeEvent::TaskReceived { task_id: format!("task{}", n) });
+        }
+
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(message) = socket.next().await {
+                if matches!(message, Ok(Message::Close(_)) | Err(_)) {
+                    break;
+                }
+            }
+        }).await;
+        assert!(closed.is_ok());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(parse_filter(None), None);
+        assert_eq!(parse_filter(Some("since=5")), None);
+        let filter = parse_filter(Some("since=5&types=task_completed,task_failed")).unwrap();
+        assert_eq!(filter.len(), 2);
+        assert!(filter.contains("task_failed"));
+    }
+}
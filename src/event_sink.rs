@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::event_feed::NodeEvent;
+
+/// Subjects events are published under unless overridden on the command line.
+/// Each event goes to `<prefix>.<type>`, e.g. `omnitensor.events.task_completed`.
+pub const DEFAULT_SUBJECT_PREFIX: &str = "omnitensor.events";
+
+/// Events held while the sink is unreachable; the oldest are dropped beyond this.
+const DEFAULT_MAX_BUFFERED: usize = 10_000;
+/// Longest a single publish may take before it counts as failed.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// External streaming system node events are mirrored into.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Publishes one serialized event under `subject`.
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> Result<()>;
+}
+
+/// Publishes events to a NATS server. The client reconnects on its own when
+/// the connection drops.
+pub struct NatsSink {
+    client: async_nats::Client,
+}
+
+impl NatsSink {
+    /// Creates the client without waiting for the server: if it can't be
+    /// reached yet, the client keeps connecting in the background and events
+    /// are buffered by the forwarder meanwhile. Only an unusable `url` fails.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect(url).await
+            .with_context(|| format!("Failed to connect to NATS at {}", url))?;
+        info!("Publishing events to NATS at {}", url);
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> Result<()> {
+        self.client.publish(subject.to_string(), payload.into()).await?;
+        Ok(())
+    }
+}
+
+/// Moves events from the `EventFeed` to an `EventSink`. Events are buffered
+/// while the sink is failing and retried with exponential backoff, so a slow
+/// or unreachable sink never holds up the main loop.
+pub struct EventForwarder {
+    sink: Arc<dyn EventSink>,
+    subject_prefix: String,
+    max_buffered: usize,
+    retry_backoff: Duration,
+}
+
+impl EventForwarder {
+    pub fn new(sink: Arc<dyn EventSink>) -> Self {
+        Self {
+            sink,
+            subject_prefix: DEFAULT_SUBJECT_PREFIX.to_string(),
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            retry_backoff: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_subject_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.subject_prefix = prefix.into().trim_end_matches('.').to_string();
+        self
+    }
+
+    /// Sets how many events are held while the sink is failing, and the delay
+    /// before the first retry, which doubles up to 30s while failures persist.
+    pub fn with_buffer(mut self, max_buffered: usize, retry_backoff: Duration) -> Self {
+        self.max_buffered = max_buffered.max(1);
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    fn subject(&self, event: &NodeEvent) -> String {
+        format!("{}.{}", self.subject_prefix, event.kind())
+    }
+
+    fn buffer(&self, pending: &mut VecDeque<NodeEvent>, event: NodeEvent) {
+        if pending.len() >= self.max_buffered {
+            pending.pop_front();
+            warn!("Event sink buffer full, dropping the oldest event");
+        }
+        pending.push_back(event);
+    }
+
+    /// Publishes `event`. The returned future only borrows the forwarder, so
+    /// the event itself can be set aside while the publish is in flight.
+    fn publish(&self, event: &NodeEvent) -> BoxFuture<'_, Result<()>> {
+        let subject = self.subject(event);
+        let payload = serde_json::to_vec(event);
+        Box::pin(async move {
+            tokio::time::timeout(PUBLISH_TIMEOUT, self.sink.publish(&subject, payload?)).await
+                .context("Timed out publishing event")?
+        })
+    }
+
+    /// Forwards `events` until the feed is dropped and everything buffered has
+    /// been published. The feed keeps being read while a publish is in flight,
+    /// so a slow sink fills this forwarder's buffer rather than making it lag
+    /// the feed.
+    pub async fn run(self, mut events: broadcast::Receiver<NodeEvent>) {
+        let mut pending = VecDeque::new();
+        let mut backoff = self.retry_backoff;
+        let mut retry_at: Option<Instant> = None;
+        let mut closed = false;
+        // The event being published, taken off the front of `pending` meanwhile
+        let mut publishing: Option<(NodeEvent, BoxFuture<'_, Result<()>>)> = None;
+
+        loop {
+            if publishing.is_none() && retry_at.is_none() {
+                match pending.pop_front() {
+                    Some(event) => {
+                        let publish = self.publish(&event);
+                        publishing = Some((event, publish));
+                    },
+                    None if closed => break,
+                    None => {},
+                }
+            }
+
+            let wake_at = retry_at;
+            let retry = async move {
+                match wake_at {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                received = events.recv(), if !closed => match received {
+                    Ok(event) => self.buffer(&mut pending, event),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Event sink fell behind the feed, {} event(s) dropped", missed);
+                    },
+                    Err(broadcast::error::RecvError::Closed) => closed = true,
+                },
+                result = async { publishing.as_mut().map(|(_, publish)| publish).expect("branch enabled only while publishing").await }, if publishing.is_some() => {
+                    let (event, _) = publishing.take().expect("branch enabled only while publishing");
+                    match result {
+                        Ok(()) => {
+                            debug!("Published {} event to the event sink", event.kind());
+                            backoff = self.retry_backoff;
+                        },
+                        Err(e) => {
+                            warn!("Failed to publish event, retrying in {:?} with {} event(s) buffered: {:#}", backoff, pending.len() + 1, e);
+                            // Back at the front, unless newer events have filled the buffer meanwhile
+                            if pending.len() < self.max_buffered {
+                                pending.push_front(event);
+                            } else {
+                                warn!("Event sink buffer full, dropping the oldest event");
+                            }
+                            retry_at = Some(Instant::now() + backoff);
+                            backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                        },
+                    }
+                },
+                _ = retry => retry_at = None,
+            }
+        }
+        info!("Event sink forwarder stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::sync::Notify;
+
+    use crate::event_feed::EventFeed;
+
+    /// In-memory sink recording what it's given; fails the first `failures` publishes.
+    #[derive(Default)]
+    struct FakeSink {
+        published: Mutex<Vec<(String, NodeEvent)>>,
+        failures: AtomicUsize,
+    }
+
+    impl FakeSink {
+        fn failing(failures: usize) -> Self {
+            Self { failures: AtomicUsize::new(failures), ..Self::default() }
+        }
+
+        fn published(&self) -> Vec<(String, NodeEvent)> {
+            self.published.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for FakeSink {
+        async fn publish(&self, subject: &str, payload: Vec<u8>) -> Result<()> {
+            if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                anyhow::bail!("connection refused");
+            }
+            self.published.lock().unwrap().push((subject.to_string(), serde_json::from_slice(&payload)?));
+            Ok(())
+        }
+    }
+
+    fn completed(n: usize) -> NodeEvent {
+        NodeEvent::TaskCompleted { task_id: format!("task{}", n), result_hash: "ab".repeat(32) }
+    }
+
+    #[tokio::test]
+    async fn test_events_published_to_sink() {
+        let feed = EventFeed::new();
+        let sink = Arc::new(FakeSink::default());
+        let forwarder = EventForwarder::new(sink.clone()).with_subject_prefix("acme.node1.");
+        let handle = tokio::spawn(forwarder.run(feed.subscribe()));
+
+        feed.publish(completed(1));
+        feed.publish(NodeEvent::TaskFailed { task_id: "task2".to_string(), error: "timed out".to_string() });
+        drop(feed);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        assert_eq!(sink.published(), vec![
+            ("acme.node1.task_completed".to_string(), completed(1)),
+            ("acme.node1.task_failed".to_string(), NodeEvent::TaskFailed { task_id: "task2".to_string(), error: "timed out".to_string() }),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink_buffers_and_retries() {
+        let feed = EventFeed::new();
+        let sink = Arc::new(FakeSink::failing(3));
+        let forwarder = EventForwarder::new(sink.clone()).with_buffer(100, Duration::from_millis(10));
+        let handle = tokio::spawn(forwarder.run(feed.subscribe()));
+
+        // Publishing to the feed never waits on the sink
+        for n in 0..5 {
+            feed.publish(completed(n));
+        }
+        drop(feed);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        let published: Vec<_> = sink.published().into_iter().map(|(_, event)| event).collect();
+        assert_eq!(published, (0..5).map(completed).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_drops_oldest_events_when_full() {
+        let feed = EventFeed::new();
+        let sink = Arc::new(FakeSink::failing(1));
+        let forwarder = EventForwarder::new(sink.clone()).with_buffer(2, Duration::from_millis(200));
+        let handle = tokio::spawn(forwarder.run(feed.subscribe()));
+
+        feed.publish(completed(0));
+        // Let the first publish fail, so the rest pile up during the backoff
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for n in 1..4 {
+            feed.publish(completed(n));
+        }
+        drop(feed);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        let published: Vec<_> = sink.published().into_iter().map(|(_, event)| event).collect();
+        assert_eq!(published, vec![completed(2), completed(3)]);
+    }
+
+    /// Sink whose first publish blocks until `release` is notified.
+    #[derive(Default)]
+    struct StalledSink {
+        inner: FakeSink,
+        release: Notify,
+        stalled: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EventSink for StalledSink {
+        async fn publish(&self, subject: &str, payload: Vec<u8>) -> Result<()> {
+            if self.stalled.fetch_add(1, Ordering::SeqCst) == 0 {
+                self.release.notified().await;
+            }
+            self.inner.publish(subject, payload).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed_read_while_publish_in_flight() {
+        let feed = EventFeed::new();
+        let sink = Arc::new(StalledSink::default());
+        let handle = tokio::spawn(EventForwarder::new(sink.clone()).run(feed.subscribe()));
+
+        // Far more events than the feed holds per subscriber arrive while the
+        // first publish is stuck
+        let count = 1_000;
+        for n in 0..count {
+            feed.publish(completed(n));
+            tokio::task::yield_now().await;
+        }
+        sink.release.notify_one();
+        drop(feed);
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        let published: Vec<_> = sink.inner.published().into_iter().map(|(_, event)| event).collect();
+        assert_eq!(published, (0..count).map(completed).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier, SECRET_KEY_LENGTH};
+use rand::RngCore;
+use thiserror::Error;
+use tracing::info;
+
+/// Directory the node keeps its identity key in unless overridden on the command line.
+pub const DEFAULT_KEY_DIR: &str = "data/keys";
+
+const KEY_FILE: &str = "node_key";
+
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error("Failed to access node key at {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid node key at {0}")]
+    InvalidKey(PathBuf),
+    #[error("Invalid node id: {0}")]
+    InvalidNodeId(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+}
+
+/// Long-lived ed25519 identity of this node. The node id is the hex-encoded
+/// public key, so peers can verify signatures from the id alone.
+pub struct NodeIdentity {
+    keypair: Keypair,
+    node_id: String,
+}
+
+impl NodeIdentity {
+    /// Loads the key stored in `key_dir`, generating and persisting a new one
+    /// on first start.
+    pub fn load_or_generate(key_dir: &Path) -> Result<Self, IdentityError> {
+        let path = key_dir.join(KEY_FILE);
+        let io_error = |source| IdentityError::Io { path: path.clone(), source };
+
+        let secret = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let bytes = hex::decode(contents.trim()).map_err(|_| IdentityError::InvalidKey(path.clone()))?;
+                SecretKey::from_bytes(&bytes).map_err(|_| IdentityError::InvalidKey(path.clone()))?
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut bytes = [0u8; SECRET_KEY_LENGTH];
+                rand::rngs::OsRng.fill_bytes(&mut bytes);
+                let secret = SecretKey::from_bytes(&bytes).map_err(|_| IdentityError::InvalidKey(path.clone()))?;
+                fs::create_dir_all(key_dir).map_err(io_error)?;
+                write_private(&path, &hex::encode(secret.as_bytes())).map_err(io_error)?;
+                info!("Generated new node key at {}", path.display());
+                secret
+            },
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let public = PublicKey::from(&secret);
+        Ok(Self {
+            node_id: hex::encode(public.as_bytes()),
+            keypair: Keypair { secret, public },
+        })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.keypair.public
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.keypair.sign(message)
+    }
+}
+
+/// Checks that `signature` over `message` was made by the node with `node_id`.
+pub fn verify(node_id: &str, message: &[u8], signature: &Signature) -> Result<(), IdentityError> {
+    let bytes = hex::decode(node_id).map_err(|_| IdentityError::InvalidNodeId(node_id.to_string()))?;
+    let public = PublicKey::from_bytes(&bytes).map_err(|_| IdentityError::InvalidNodeId(node_id.to_string()))?;
+    public.verify(message, signature).map_err(|_| IdentityError::InvalidSignature)
+}
+
+/// Writes `contents` to `path`, readable only by the owner where supported.
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_node_id_is_stable_across_starts() {
+        let dir = tempdir().unwrap();
+        let key_dir = dir.path().join("keys");
+
+        let first = NodeIdentity::load_or_generate(&key_dir).unwrap();
+        let second = NodeIdentity::load_or_generate(&key_dir).unwrap();
+        assert_eq!(first.node_id(), second.node_id());
+        assert_eq!(first.node_id(), hex::encode(first.public_key().as_bytes()));
+
+        let other = NodeIdentity::load_or_generate(&dir.path().join("other")).unwrap();
+        assert_ne!(first.node_id(), other.node_id());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempdir().unwrap();
+        let identity = NodeIdentity::load_or_generate(dir.path()).unwrap();
+
+        let signature = identity.sign(b"block 42");
+        assert!(verify(identity.node_id(), b"block 42", &signature).is_ok());
+        assert!(matches!(verify(identity.node_id(), b"block 43", &signature), Err(IdentityError::InvalidSignature)));
+        assert!(matches!(verify("not-hex", b"block 42", &signature), Err(IdentityError::InvalidNodeId(_))));
+    }
+
+    #[test]
+    fn test_corrupt_key_is_rejected() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(KEY_FILE), "zz").unwrap();
+        assert!(matches!(NodeIdentity::load_or_generate(dir.path()), Err(IdentityError::InvalidKey(_))));
+    }
+}
@@ -0,0 +1,156 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::EnvFilter;
+
+/// Verbosity used when neither `RUST_LOG` nor `--log-level` is given.
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// How log lines are formatted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Pretty,
+    /// One JSON object per line with `timestamp`, `level`, `target`, the
+    /// event's fields and the enclosing spans (e.g. `task_id`).
+    Json,
+}
+
+/// Builds the node's log subscriber, writing lines that pass `filter` to `writer`.
+pub fn subscriber<W>(format: LogFormat, filter: EnvFilter, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .finish()),
+    }
+}
+
+/// Filter directives to log with. A non-empty `RUST_LOG` wins over the
+/// configured `log_level`, e.g. `info,network=debug`.
+pub fn filter_directives(rust_log: Option<&str>, log_level: Option<&str>) -> String {
+    match (rust_log.map(str::trim), log_level) {
+        (Some(rust_log), _) if !rust_log.is_empty() => rust_log.to_string(),
+        (_, Some(log_level)) => expand_module_directives(log_level),
+        _ => DEFAULT_LOG_LEVEL.to_string(),
+    }
+}
+
+/// Lets `network=debug` also match this crate's `omnitensor_node::network`
+/// target, which is what logs from that module carry.
+fn expand_module_directives(log_level: &str) -> String {
+    let crate_name = module_path!().split("::").next().unwrap_or_default();
+    log_level.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .flat_map(|directive| {
+            let module_override = directive.split_once('=')
+                .filter(|(target, _)| !target.contains("::") && !target.contains('['))
+                .map(|(target, level)| format!("{}::{}={}", crate_name, target, level));
+            std::iter::once(directive.to_string()).chain(module_override)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Installs the log subscriber for the whole process, also capturing records
+/// from the `log` crate. Must run before any component logs, or those lines
+/// are lost.
+pub fn init(format: LogFormat, log_level: Option<&str>) -> Result<(), ParseError> {
+    let rust_log = std::env::var(EnvFilter::DEFAULT_ENV).ok();
+    let filter = EnvFilter::try_new(filter_directives(rust_log.as_deref(), log_level))?;
+    if let Err(e) = subscriber(format, filter, std::io::stdout).try_init() {
+        eprintln!("Failed to install log subscriber: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture(format: LogFormat) -> String {
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        tracing::subscriber::with_default(subscriber(format, EnvFilter::new("info"), move || writer.clone()), || {
+            let span = tracing::info_span!("task", task_id = "task1", trace_id = "abc");
+            let _entered = span.enter();
+            tracing::error!(gpu = 0, "Task failed");
+        });
+        let bytes = buffer.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines_carry_fields_and_span_context() {
+        let output = capture(LogFormat::Json);
+        let line = output.lines().next().expect("one log line");
+        let json: serde_json::Value = serde_json::from_str(line).unwrap();
+
+        assert!(json["timestamp"].is_string());
+        assert_eq!(json["level"], "ERROR");
+        assert_eq!(json["target"], module_path!());
+        assert_eq!(json["message"], "Task failed");
+        assert_eq!(json["gpu"], 0);
+        assert_eq!(json["span"]["name"], "task");
+        assert_eq!(json["span"]["task_id"], "task1");
+        assert_eq!(json["spans"][0]["trace_id"], "abc");
+    }
+
+    #[test]
+    fn test_filter_directives() {
+        assert_eq!(filter_directives(None, None), "info");
+        assert_eq!(filter_directives(None, Some("warn")), "warn");
+        assert_eq!(
+            filter_directives(None, Some("info, network=debug,consensus=info")),
+            "info,network=debug,omnitensor_node::network=debug,consensus=info,omnitensor_node::consensus=info"
+        );
+        // Fully qualified targets are left alone
+        assert_eq!(filter_directives(None, Some("tokio::net=trace")), "tokio::net=trace");
+
+        // An explicit RUST_LOG takes precedence, unless it's empty
+        assert_eq!(filter_directives(Some("debug"), Some("warn")), "debug");
+        assert_eq!(filter_directives(Some(" "), Some("warn")), "warn");
+
+        assert!(EnvFilter::try_new(filter_directives(None, Some("info,network=debug"))).is_ok());
+        assert!(EnvFilter::try_new(filter_directives(None, Some("network=loud"))).is_err());
+    }
+
+    #[test]
+    fn test_pretty_lines_are_not_json() {
+        let output = capture(LogFormat::Pretty);
+        assert!(output.contains("Task failed"));
+        assert!(output.contains("task_id=\"task1\"") || output.contains("task_id=task1"));
+        assert!(serde_json::from_str::<serde_json::Value>(output.lines().next().unwrap()).is_err());
+    }
+}
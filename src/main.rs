@@ -1,16 +1,36 @@
 use tokio;
-use tracing::{info, error};
-use clap::{App, Arg};
+use tracing::{info, error, warn, Instrument};
 use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
 use tokio::sync::Mutex;
 
+mod cli;
 mod config;
+mod event_feed;
+mod event_sink;
+mod identity;
+mod logging;
+mod metrics;
+mod node_error;
+mod role;
+mod settings;
+mod shutdown;
+mod webhook;
 mod network;
 mod consensus;
 mod storage;
 mod compute;
 
+use crate::cli::{Command, StartArgs};
 use crate::config::Config;
+use crate::node_error::{log_unless_fatal, NodeError};
+use crate::event_feed::{EventFeed, EventServer, NodeEvent};
+use crate::event_sink::{EventForwarder, NatsSink};
+use crate::identity::NodeIdentity;
+use crate::role::{NodeRole, Subsystem};
+use crate::settings::NodeSettings;
+use crate::shutdown::InFlightTasks;
 use crate::network::Network;
 use crate::network::Message as NetworkMessage;
 use crate::consensus::Consensus;
@@ -20,75 +40,124 @@ use crate::compute::ComputeManager;
 use crate::compute::{Event as ComputeEvent, Task, TaskStatus};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
-
+async fn main() -> Result<(), NodeError> {
     // Parse command line arguments
-    let matches = App::new("OmniTensor Node")
-        .version("0.1.0")
-        .author("OmniTensor Team")
-        .about("Decentralized AI Infrastructure Node")
-        .arg(Arg::with_name("config")
-            .short("c")
-            .long("config")
-            .value_name("FILE")
-            .help("Sets a custom config file")
-            .takes_value(true))
-        .get_matches();
+    let cli = cli::parse_cli_args();
+
+    let start = match cli.command {
+        Command::Start(start) => start,
+        Command::Version => {
+            println!("omnitensor-node {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        },
+    };
+
+    // Initialize logging before any component starts
+    let settings = NodeSettings::from_file(&cli.config)?;
+    logging::init(settings.log_format(cli.log_format), settings.log_level(cli.log_level.as_deref())).map_err(NodeError::config)?;
 
     // Load configuration
-    let config_path = matches.value_of("config").unwrap_or("config/default.toml");
-    let config = Config::from_file(config_path)?;
+    let config_path = cli.config.display().to_string();
+    let config = Config::from_file(&config_path).map_err(NodeError::config)?;
+
+    let role = settings.role(start.role);
+    info!("Starting OmniTensor node as {:?} with config: {}", role, config_path);
+
+    // Load the node's identity, generating it on first start
+    let identity = NodeIdentity::load_or_generate(&start.key_dir).map_err(NodeError::config)?;
+    info!("Node id: {}", identity.node_id());
+
+    // Everything the node logs from here on carries its id
+    let span = tracing::info_span!("node", node_id = %identity.node_id());
+    run_node(config, role, start).instrument(span).await
+}
 
-    info!("Starting OmniTensor node with config: {}", config_path);
+async fn run_node(config: Config, role: NodeRole, start: StartArgs) -> Result<(), NodeError> {
+    let drain_timeout = Duration::from_secs(start.drain_timeout);
 
     // Initialize components
-    let storage = Arc::new(Mutex::new(Storage::new(&config.storage)?));
-    let network = Arc::new(Network::new(&config.network)?);
-    let consensus = Arc::new(Consensus::new(&config.consensus, network.clone(), storage.clone())?);
-    let compute_manager = Arc::new(ComputeManager::new(&config.compute)?);
+    let storage = Arc::new(Mutex::new(Storage::new(&config.storage).map_err(NodeError::storage)?));
+    let network = Arc::new(Network::new(&config.network).map_err(NodeError::network)?);
+    let consensus = Arc::new(Consensus::new(&config.consensus, network.clone(), storage.clone()).map_err(NodeError::consensus)?);
+    let compute_manager = Arc::new(ComputeManager::new(&config.compute).map_err(NodeError::compute)?);
 
     // Start network services
-    network.start().await?;
+    network.start().await.map_err(NodeError::network)?;
+
+    // Start the consensus engine and compute manager as the role calls for.
+    // Compute-only nodes don't produce or vote on blocks; their completions
+    // are broadcast to connected validators instead.
+    role::start_subsystems(role, consensus.as_ref(), compute_manager.as_ref()).await?;
+    let submit_to = role.runs_consensus().then_some(&consensus);
 
-    // Start consensus engine
-    consensus.start().await?;
+    // Stream main loop events to dashboards
+    let events = EventFeed::new();
+    let event_server = EventServer::bind(&start.events_addr, events.clone()).await?;
+    let (stop_events, events_stopped) = tokio::sync::oneshot::channel::<()>();
+    let events_handle = tokio::spawn(event_server.serve(async { events_stopped.await.ok(); }).in_current_span());
 
-    // Start compute manager
-    compute_manager.start().await?;
+    // Mirror the same events into the operator's NATS, off the main loop
+    let sink_handle = match &start.nats_url {
+        Some(url) => match NatsSink::connect(url).await {
+            Ok(sink) => {
+                let forwarder = EventForwarder::new(Arc::new(sink))
+                    .with_subject_prefix(start.nats_subject_prefix.clone());
+                Some(tokio::spawn(forwarder.run(events.subscribe()).in_current_span()))
+            },
+            Err(e) => {
+                // The sink is optional; the node runs without it
+                warn!("Not mirroring events to NATS: {:#}", e);
+                None
+            },
+        },
+        None => None,
+    };
 
-    // Main event loop
+    // Main event loop; recoverable errors are logged, fatal ones stop the node
+    let in_flight = InFlightTasks::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut fatal_error = None;
     loop {
         tokio::select! {
+            _ = &mut shutdown => {
+                info!("Received shutdown signal");
+                break;
+            }
             Some(event) = network.next_event() => {
                 match event {
                     Ok(network_event) => {
                         // Handle network events
-                        if let Err(e) = handle_network_event(network_event, &consensus, &compute_manager).await {
-                            error!("Error handling network event: {}", e);
+                        let result = handle_network_event(network_event, &consensus, &compute_manager).await;
+                        if let Err(e) = log_unless_fatal(result, "network event") {
+                            fatal_error = Some(e);
+                            break;
                         }
                     },
                     Err(e) => error!("Network error: {}", e),
                 }
             }
-            Some(event) = consensus.next_event() => {
+            Some(event) = consensus.next_event(), if role.runs_consensus() => {
                 match event {
                     Ok(consensus_event) => {
                         // Handle consensus events
-                        if let Err(e) = handle_consensus_event(consensus_event, &network, &compute_manager).await {
-                            error!("Error handling consensus event: {}", e);
+                        let result = handle_consensus_event(consensus_event, &network, &compute_manager).await;
+                        if let Err(e) = log_unless_fatal(result, "consensus event") {
+                            fatal_error = Some(e);
+                            break;
                         }
                     },
                     Err(e) => error!("Consensus error: {}", e),
                 }
             }
-            Some(event) = compute_manager.next_event() => {
+            Some(event) = compute_manager.next_event(), if role.runs_compute() => {
                 match event {
                     Ok(compute_event) => {
                         // Handle compute events
-                        if let Err(e) = handle_compute_event(compute_event, &network, &consensus).await {
-                            error!("Error handling compute event: {}", e);
+                        let result = handle_compute_event(compute_event, &compute_manager, &network, submit_to, &storage, &events, &in_flight).await;
+                        if let Err(e) = log_unless_fatal(result, "compute event") {
+                            fatal_error = Some(e);
+                            break;
                         }
                     },
                     Err(e) => error!("Compute error: {}", e),
@@ -98,20 +167,81 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Graceful shutdown
+    // Graceful shutdown: turn new tasks away and keep handling compute events
+    // until the running ones have reported back, unless signalled again
+    if let Some(e) = &fatal_error {
+        error!("Stopping after fatal error: {}", e);
+    }
     info!("Shutting down OmniTensor node");
-    compute_manager.stop().await?;
-    consensus.stop().await?;
-    network.stop().await?;
+    if role.runs_compute() {
+        let drain = shutdown::drain(
+            &in_flight,
+            drain_timeout,
+            || compute_manager.next_event(),
+            |event| handle_draining_event(event, &compute_manager, &network, submit_to, &storage, &events, &in_flight),
+        );
+        tokio::select! {
+            drained = drain => {
+                if !drained {
+                    warn!("Stopping with {} tasks still running after {:?}", in_flight.len(), drain_timeout);
+                }
+            }
+            _ = shutdown_signal() => {
+                warn!("Received second shutdown signal, exiting immediately");
+                std::process::exit(1);
+            }
+        }
+    }
+    stop_events.send(()).ok();
+    events_handle.await?;
+    // Give the sink a moment to flush what's still buffered
+    drop(events);
+    if let Some(sink_handle) = sink_handle {
+        if tokio::time::timeout(Duration::from_secs(5), sink_handle).await.is_err() {
+            warn!("Dropping events the sink hasn't accepted yet");
+        }
+    }
+    if role.runs_compute() {
+        compute_manager.stop().await.map_err(NodeError::compute)?;
+    }
+    if role.runs_consensus() {
+        consensus.stop().await.map_err(NodeError::consensus)?;
+    }
+    network.stop().await.map_err(NodeError::network)?;
 
-    Ok(())
+    match fatal_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Resolves on SIGINT, or on SIGTERM where available.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(e) => error!("Failed to install SIGTERM handler: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to listen for SIGINT: {}", e);
+        std::future::pending::<()>().await;
+    }
 }
 
 async fn handle_network_event(
     event: network::Event,
     consensus: &Arc<Consensus>,
     compute_manager: &Arc<ComputeManager>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), NodeError> {
     // TODO: Implement network event handling
     Ok(())
 }
@@ -120,73 +250,139 @@ async fn handle_consensus_event(
     event: consensus::Event,
     network: &Arc<Network>,
     compute_manager: &Arc<ComputeManager>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), NodeError> {
     // TODO: Implement consensus event handling
     Ok(())
 }
 
+#[async_trait]
+impl Subsystem for Consensus {
+    async fn start(&self) -> Result<(), NodeError> {
+        Consensus::start(self).await.map_err(NodeError::consensus)
+    }
+}
+
+#[async_trait]
+impl Subsystem for ComputeManager {
+    async fn start(&self) -> Result<(), NodeError> {
+        ComputeManager::start(self).await.map_err(NodeError::compute)
+    }
+}
+
+/// Handles a compute event that arrives while draining. Errors are logged
+/// rather than returned since the node is stopping either way.
+async fn handle_draining_event<E: std::fmt::Display>(
+    event: Result<ComputeEvent, E>,
+    compute_manager: &Arc<ComputeManager>,
+    network: &Arc<Network>,
+    consensus: Option<&Arc<Consensus>>,
+    storage: &Arc<Mutex<Storage>>,
+    events: &EventFeed,
+    in_flight: &InFlightTasks,
+) {
+    match event {
+        Ok(compute_event) => {
+            if let Err(e) = handle_compute_event(compute_event, compute_manager, network, consensus, storage, events, in_flight).await {
+                error!("Failed to handle compute event while draining: {}", e);
+            }
+        },
+        Err(e) => error!("Compute error: {}", e),
+    }
+}
+
+/// Handles an event from the compute manager. Transactions are submitted to
+/// `consensus` when this node runs it; otherwise the network broadcast is
+/// what carries them to the validators.
+#[tracing::instrument(skip_all, fields(task_id = tracing::field::Empty))]
 async fn handle_compute_event(
     event: ComputeEvent,
+    compute_manager: &Arc<ComputeManager>,
     network: &Arc<Network>,
-    consensus: &Arc<Consensus>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    consensus: Option<&Arc<Consensus>>,
+    storage: &Arc<Mutex<Storage>>,
+    events: &EventFeed,
+    in_flight: &InFlightTasks,
+) -> Result<(), NodeError> {
     match event {
         ComputeEvent::TaskCompleted(task) => {
+            tracing::Span::current().record("task_id", &tracing::field::display(&task.id));
             info!("Task completed: {}", task.id);
+            in_flight.finished(&task.id);
+            events.publish(NodeEvent::TaskCompleted { task_id: task.id.to_string(), result_hash: task.result_hash.to_string() });
             
             // Update task status in local storage
-            consensus.storage.lock().await.update_task_status(&task.id, TaskStatus::Completed)?;
+            storage.lock().await.update_task_status(&task.id, TaskStatus::Completed)
+                .map_err(NodeError::storage)?;
             
             // Create a transaction for the completed task
             let transaction = Transaction::new_task_completion(task.id, task.result_hash);
             
             // Submit the transaction to the consensus layer
-            consensus.submit_transaction(transaction).await?;
+            if let Some(consensus) = consensus {
+                consensus.submit_transaction(transaction).await.map_err(NodeError::consensus)?;
+            }
             
             // Notify the network about the completed task
             let message = NetworkMessage::TaskCompleted { 
                 task_id: task.id, 
                 result_hash: task.result_hash 
             };
-            network.broadcast(message).await?;
+            network.broadcast(message).await.map_err(NodeError::network)?;
         },
         ComputeEvent::TaskFailed(task_id, error) => {
+            tracing::Span::current().record("task_id", &tracing::field::display(&task_id));
             error!("Task failed: {}. Error: {}", task_id, error);
+            in_flight.finished(&task_id);
+            events.publish(NodeEvent::TaskFailed { task_id: task_id.to_string(), error: error.to_string() });
             
             // Update task status in local storage
-            consensus.storage.lock().await.update_task_status(&task_id, TaskStatus::Failed)?;
+            storage.lock().await.update_task_status(&task_id, TaskStatus::Failed)
+                .map_err(NodeError::storage)?;
             
             // Create a transaction for the failed task
             let transaction = Transaction::new_task_failure(task_id, error);
             
             // Submit the transaction to the consensus layer
-            consensus.submit_transaction(transaction).await?;
+            if let Some(consensus) = consensus {
+                consensus.submit_transaction(transaction).await.map_err(NodeError::consensus)?;
+            }
             
             // Notify the network about the failed task
             let message = NetworkMessage::TaskFailed { task_id, error };
-            network.broadcast(message).await?;
+            network.broadcast(message).await.map_err(NodeError::network)?;
         },
         ComputeEvent::NewTaskReceived(task) => {
+            tracing::Span::current().record("task_id", &tracing::field::display(&task.id));
             info!("New task received: {}", task.id);
+            events.publish(NodeEvent::TaskReceived { task_id: task.id.to_string() });
             
             // Verify if the node has capacity to handle the task
-            if compute_manager.has_capacity() {
+            if !in_flight.accepting() {
+                // Reject the task while draining for shutdown
+                let message = NetworkMessage::TaskRejected { 
+                    task_id: task.id, 
+                    reason: "Shutting down".to_string() 
+                };
+                network.broadcast(message).await.map_err(NodeError::network)?;
+            } else if compute_manager.has_capacity() {
                 // Accept the task
-                compute_manager.accept_task(task).await?;
+                compute_manager.accept_task(task).await.map_err(NodeError::compute)?;
+                in_flight.started(&task.id);
                 
                 // Update task status in local storage
-                consensus.storage.lock().await.update_task_status(&task.id, TaskStatus::InProgress)?;
+                storage.lock().await.update_task_status(&task.id, TaskStatus::InProgress)
+                    .map_err(NodeError::storage)?;
                 
                 // Notify the network that we've accepted the task
                 let message = NetworkMessage::TaskAccepted { task_id: task.id };
-                network.broadcast(message).await?;
+                network.broadcast(message).await.map_err(NodeError::network)?;
             } else {
                 // Reject the task if we don't have capacity
                 let message = NetworkMessage::TaskRejected { 
                     task_id: task.id, 
                     reason: "No capacity".to_string() 
                 };
-                network.broadcast(message).await?;
+                network.broadcast(message).await.map_err(NodeError::network)?;
             }
         },
         ComputeEvent::ResourceUsageUpdate(usage) => {
@@ -197,25 +393,28 @@ async fn handle_compute_event(
                 memory_usage: usage.memory, 
                 gpu_usage: usage.gpu 
             };
-            network.broadcast(message).await?;
+            network.broadcast(message).await.map_err(NodeError::network)?;
             
             // If resource usage is high, consider offloading tasks
             if usage.is_high() {
-                compute_manager.consider_offloading().await?;
+                compute_manager.consider_offloading().await.map_err(NodeError::compute)?;
             }
         },
         ComputeEvent::ModelUpdated(model_id, new_version) => {
             info!("Model updated: {} to version {}", model_id, new_version);
+            events.publish(NodeEvent::ModelUpdated { model_id: model_id.to_string(), version: new_version.to_string() });
             
             // Create a transaction for the model update
             let transaction = Transaction::new_model_update(model_id, new_version);
             
             // Submit the transaction to the consensus layer
-            consensus.submit_transaction(transaction).await?;
+            if let Some(consensus) = consensus {
+                consensus.submit_transaction(transaction).await.map_err(NodeError::consensus)?;
+            }
             
             // Notify the network about the model update
             let message = NetworkMessage::ModelUpdated { model_id, new_version };
-            network.broadcast(message).await?;
+            network.broadcast(message).await.map_err(NodeError::network)?;
         },
     }
 
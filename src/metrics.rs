@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the task execution time histogram.
+const TASK_EXECUTION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+/// Distribution of observed values, with cumulative counts per upper bound.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    /// `(upper bound, observations <= upper bound)`, in ascending bound order.
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+/// Monotonically increasing count.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Value that can go up and down, such as a queue depth.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1);
+    }
+
+    pub fn add(&self, delta: i64) {
+        self.0.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of observations over fixed buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    /// Observations per bucket; values above the last bound only count toward `count`.
+    buckets: Vec<AtomicU64>,
+    sum_bits: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    /// Creates a histogram with the given bucket upper bounds, which are sorted
+    /// and deduplicated.
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds: Vec<f64> = bounds.iter().copied().filter(|b| b.is_finite()).collect();
+        bounds.sort_by(|a, b| a.total_cmp(b));
+        bounds.dedup();
+        let buckets = bounds.iter().map(|_| AtomicU64::new(0)).collect();
+        Self {
+            bounds,
+            buckets,
+            sum_bits: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = self.sum_bits.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current state with cumulative bucket counts.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self.bounds.iter()
+            .zip(&self.buckets)
+            .map(|(&bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (bound, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            sum: f64::from_bits(self.sum_bits.load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Named metrics, created on first use so new ones don't need bespoke plumbing.
+#[derive(Debug, Default)]
+pub struct Registry {
+    counters: RwLock<BTreeMap<String, Arc<Counter>>>,
+    gauges: RwLock<BTreeMap<String, Arc<Gauge>>>,
+    histograms: RwLock<BTreeMap<String, Arc<Histogram>>>,
+}
+
+impl Registry {
+    pub fn counter(&self, name: &str) -> Arc<Counter> {
+        get_or_insert(&self.counters, name, Counter::default)
+    }
+
+    pub fn gauge(&self, name: &str) -> Arc<Gauge> {
+        get_or_insert(&self.gauges, name, Gauge::default)
+    }
+
+    /// Returns the histogram registered under `name`, creating it with `bounds`
+    /// if it doesn't exist yet.
+    pub fn histogram(&self, name: &str, bounds: &[f64]) -> Arc<Histogram> {
+        get_or_insert(&self.histograms, name, || Histogram::new(bounds))
+    }
+}
+
+fn get_or_insert<T>(metrics: &RwLock<BTreeMap<String, Arc<T>>>, name: &str, create: impl FnOnce() -> T) -> Arc<T> {
+    if let Some(metric) = metrics.read().unwrap_or_else(|e| e.into_inner()).get(name) {
+        return Arc::clone(metric);
+    }
+    let mut metrics = metrics.write().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(metrics.entry(name.to_string()).or_insert_with(|| Arc::new(create())))
+}
+
+/// Node-wide metrics, backed by a `Registry`.
+pub struct MetricsCollector {
+    registry: Registry,
+    queued_tasks: Arc<Gauge>,
+    running_tasks: Arc<Gauge>,
+    overdue_tasks: Arc<Counter>,
+    task_retries: Arc<Counter>,
+    task_cache_hits: Arc<Counter>,
+    task_execution_seconds: Arc<Histogram>,
+}
+
+impl MetricsCollector {
+    pub fn new() -> Self {
+        let registry = Registry::default();
+        Self {
+            queued_tasks: registry.gauge("queued_tasks"),
+            running_tasks: registry.gauge("running_tasks"),
+            overdue_tasks: registry.counter("overdue_tasks"),
+            task_retries: registry.counter("task_retries"),
+            task_cache_hits: registry.counter("task_cache_hits"),
+            task_execution_seconds: registry.histogram("task_execution_seconds", TASK_EXECUTION_BUCKETS),
+            registry,
+        }
+    }
+
+    /// Registry for metrics without a dedicated method.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn increment_queued_tasks(&self) {
+        self.queued_tasks.inc();
+    }
+
+    pub fn decrement_queued_tasks(&self) {
+        self.queued_tasks.dec();
+    }
+
+    pub fn set_running_tasks(&self, running: usize) {
+        self.running_tasks.set(running as i64);
+    }
+
+    pub fn increment_overdue_tasks(&self) {
+        self.overdue_tasks.inc();
+    }
+
+    pub fn increment_task_retries(&self) {
+        self.task_retries.inc();
+    }
+
+    pub fn increment_task_cache_hits(&self) {
+        self.task_cache_hits.inc();
+    }
+
+    pub fn record_task_execution(&self, execution_time: Duration) {
+        self.task_execution_seconds.observe(execution_time.as_secs_f64());
+    }
+
+    pub fn queued_tasks(&self) -> u64 {
+        self.queued_tasks.get().max(0) as u64
+    }
+
+    pub fn overdue_tasks(&self) -> u64 {
+        self.overdue_tasks.get()
+    }
+
+    pub fn task_cache_hits(&self) -> u64 {
+        self.task_cache_hits.get()
+    }
+
+    pub fn task_execution_histogram(&self) -> HistogramSnapshot {
+        self.task_execution_seconds.snapshot()
+    }
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_bucketing() {
+        let histogram = Histogram::new(&[1.0, 0.1, 10.0]);
+        for value in [0.05, 0.1, 0.5, 2.0, 50.0] {
+            histogram.observe(value);
+        }
+
+        let snapshot = histogram.snapshot();
+        // Bounds are sorted; a value equal to a bound falls in that bucket
+        assert_eq!(snapshot.buckets, vec![(0.1, 2), (1.0, 3), (10.0, 4)]);
+        assert_eq!(snapshot.count, 5);
+        assert!((snapshot.sum - 52.65).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gauge_semantics() {
+        let gauge = Gauge::default();
+        gauge.set(5);
+        gauge.inc();
+        gauge.inc();
+        gauge.dec();
+        assert_eq!(gauge.get(), 6);
+        gauge.add(-10);
+        assert_eq!(gauge.get(), -4);
+        gauge.set(0);
+        assert_eq!(gauge.get(), 0);
+    }
+
+    #[test]
+    fn test_registry_returns_same_metric() {
+        let registry = Registry::default();
+        registry.counter("blocks_imported").inc();
+        registry.counter("blocks_imported").inc_by(2);
+        assert_eq!(registry.counter("blocks_imported").get(), 3);
+
+        // Bounds given after the first registration are ignored
+        registry.histogram("latency", &[1.0]).observe(0.5);
+        let snapshot = registry.histogram("latency", &[5.0]).snapshot();
+        assert_eq!(snapshot.buckets, vec![(1.0, 1)]);
+    }
+
+    #[test]
+    fn test_collector_call_sites() {
+        let metrics = MetricsCollector::new();
+        metrics.increment_queued_tasks();
+        metrics.increment_queued_tasks();
+        metrics.decrement_queued_tasks();
+        metrics.increment_overdue_tasks();
+        metrics.record_task_execution(Duration::from_millis(300));
+        metrics.set_running_tasks(3);
+
+        assert_eq!(metrics.queued_tasks(), 1);
+        assert_eq!(metrics.overdue_tasks(), 1);
+        assert_eq!(metrics.registry().gauge("running_tasks").get(), 3);
+        let histogram = metrics.task_execution_histogram();
+        assert_eq!(histogram.count, 1);
+        assert_eq!(histogram.buckets[0], (0.1, 0));
+        assert_eq!(histogram.buckets[1], (0.5, 1));
+    }
+}
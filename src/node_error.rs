@@ -0,0 +1,115 @@
+use thiserror::Error;
+use tracing::error;
+
+use crate::error::OmniTensorError;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Top-level error of the node binary. Each component's errors keep their
+/// source, so callers can tell which part of the node failed.
+#[derive(Error, Debug)]
+pub enum NodeError {
+    #[error("Configuration error: {0}")]
+    Config(#[source] BoxError),
+    #[error("Network error: {0}")]
+    Network(#[source] BoxError),
+    #[error("Consensus error: {0}")]
+    Consensus(#[source] BoxError),
+    #[error("Storage error: {0}")]
+    Storage(#[source] BoxError),
+    #[error("Compute error: {0}")]
+    Compute(#[source] BoxError),
+    #[error(transparent)]
+    Core(#[from] OmniTensorError),
+    #[error("Endpoint error: {0:#}")]
+    Endpoint(#[from] anyhow::Error),
+    #[error("Background task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+impl NodeError {
+    pub fn config(e: impl Into<BoxError>) -> Self {
+        NodeError::Config(e.into())
+    }
+
+    pub fn network(e: impl Into<BoxError>) -> Self {
+        NodeError::Network(e.into())
+    }
+
+    pub fn consensus(e: impl Into<BoxError>) -> Self {
+        NodeError::Consensus(e.into())
+    }
+
+    pub fn storage(e: impl Into<BoxError>) -> Self {
+        NodeError::Storage(e.into())
+    }
+
+    pub fn compute(e: impl Into<BoxError>) -> Self {
+        NodeError::Compute(e.into())
+    }
+
+    /// Whether the node can't keep running after this error. Failures in
+    /// handling a single network, consensus or compute event are recoverable.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            NodeError::Network(_) | NodeError::Consensus(_) | NodeError::Compute(_) | NodeError::Core(_) => false,
+            NodeError::Config(_) | NodeError::Storage(_) | NodeError::Endpoint(_) | NodeError::Join(_) => true,
+        }
+    }
+}
+
+/// Logs a recoverable error and swallows it; fatal errors are passed back.
+pub fn log_unless_fatal(result: Result<(), NodeError>, context: &str) -> Result<(), NodeError> {
+    match result {
+        Err(e) if !e.is_fatal() => {
+            error!("Error handling {}: {}", context, e);
+            Ok(())
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    use crate::event_feed::{EventFeed, EventServer};
+
+    #[test]
+    fn test_component_errors_keep_their_kind() {
+        let err = NodeError::network(io::Error::new(io::ErrorKind::ConnectionReset, "peer reset"));
+        assert!(matches!(err, NodeError::Network(_)));
+        assert!(!err.is_fatal());
+        assert_eq!(err.to_string(), "Network error: peer reset");
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::ConnectionReset);
+
+        let err = NodeError::storage("disk full");
+        assert!(matches!(err, NodeError::Storage(_)));
+        assert!(err.is_fatal());
+
+        let err: NodeError = OmniTensorError::TaskTimeout("task1".to_string()).into();
+        assert!(matches!(err, NodeError::Core(OmniTensorError::TaskTimeout(_))));
+        assert!(!err.is_fatal());
+    }
+
+    #[test]
+    fn test_log_unless_fatal() {
+        assert!(log_unless_fatal(Ok(()), "event").is_ok());
+        assert!(log_unless_fatal(Err(NodeError::consensus("stale block")), "consensus event").is_ok());
+        let fatal = log_unless_fatal(Err(NodeError::storage("corrupt database")), "compute event");
+        assert!(matches!(fatal, Err(NodeError::Storage(_))));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_bind_failure() {
+        let first = EventServer::bind("127.0.0.1:0", EventFeed::new()).await.unwrap();
+        let addr = first.local_addr().unwrap().to_string();
+
+        let err: NodeError = EventServer::bind(&addr, EventFeed::new()).await.err().unwrap().into();
+        assert!(matches!(err, NodeError::Endpoint(_)));
+        assert!(err.is_fatal());
+        assert!(err.to_string().contains(&addr));
+    }
+}
@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::node_error::NodeError;
+
+/// Which subsystems a node runs. Spelled the same on the command line and in
+/// the config file; `compute_only` is still accepted in either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeRole {
+    /// Produces and votes on blocks and executes compute tasks.
+    #[default]
+    Full,
+    /// Produces and votes on blocks but takes no compute tasks.
+    Validator,
+    /// Executes compute tasks only. The consensus engine is never started;
+    /// completions are broadcast to the validators it's connected to, which
+    /// record them on chain.
+    #[serde(alias = "compute_only")]
+    #[value(alias = "compute_only")]
+    ComputeOnly,
+}
+
+impl NodeRole {
+    /// Whether the node runs the consensus engine, producing and voting on blocks.
+    pub fn runs_consensus(self) -> bool {
+        matches!(self, NodeRole::Full | NodeRole::Validator)
+    }
+
+    /// Whether the node accepts and executes compute tasks.
+    pub fn runs_compute(self) -> bool {
+        matches!(self, NodeRole::Full | NodeRole::ComputeOnly)
+    }
+}
+
+/// A part of the node that only starts when its role calls for it.
+#[async_trait]
+pub trait Subsystem: Send + Sync {
+    async fn start(&self) -> Result<(), NodeError>;
+}
+
+/// Starts the subsystems `role` runs, consensus before compute.
+pub async fn start_subsystems(role: NodeRole, consensus: &dyn Subsystem, compute: &dyn Subsystem) -> Result<(), NodeError> {
+    if role.runs_consensus() {
+        consensus.start().await?;
+    }
+    if role.runs_compute() {
+        compute.start().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct FakeSubsystem {
+        started: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Subsystem for FakeSubsystem {
+        async fn start(&self) -> Result<(), NodeError> {
+            self.started.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    async fn boot(role: NodeRole) -> (bool, bool) {
+        let consensus = FakeSubsystem::default();
+        let compute = FakeSubsystem::default();
+        start_subsystems(role, &consensus, &compute).await.unwrap();
+        (consensus.started.load(Ordering::SeqCst), compute.started.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_compute_only_boots_without_consensus() {
+        assert_eq!(boot(NodeRole::ComputeOnly).await, (false, true));
+        assert_eq!(boot(NodeRole::Validator).await, (true, false));
+        assert_eq!(boot(NodeRole::Full).await, (true, true));
+    }
+
+    #[test]
+    fn test_role_subsystems() {
+        assert!(NodeRole::Full.runs_consensus() && NodeRole::Full.runs_compute());
+        assert!(NodeRole::Validator.runs_consensus() && !NodeRole::Validator.runs_compute());
+        // A pure GPU worker never starts the block producer
+        assert!(!NodeRole::ComputeOnly.runs_consensus() && NodeRole::ComputeOnly.runs_compute());
+    }
+
+    #[test]
+    fn test_role_names() {
+        assert_eq!(NodeRole::from_str("compute-only", false).unwrap(), NodeRole::ComputeOnly);
+        assert_eq!(NodeRole::from_str("compute_only", false).unwrap(), NodeRole::ComputeOnly);
+        assert_eq!(serde_json::to_string(&NodeRole::ComputeOnly).unwrap(), "\"compute-only\"");
+        assert_eq!(serde_json::from_str::<NodeRole>("\"compute_only\"").unwrap(), NodeRole::ComputeOnly);
+        assert_eq!(NodeRole::default(), NodeRole::Full);
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::logging::LogFormat;
+use crate::node_error::NodeError;
+use crate::role::NodeRole;
+
+/// Node-level settings the binary reads from the config file itself. The
+/// component sections are left to `Config`; keys not listed here are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct NodeSettings {
+    #[serde(default)]
+    pub node: NodeSection,
+    #[serde(default)]
+    pub logging: LoggingSection,
+}
+
+/// The `[node]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct NodeSection {
+    /// Which subsystems the node runs; `start --role` takes precedence.
+    pub role: Option<NodeRole>,
+}
+
+/// The `[logging]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct LoggingSection {
+    /// `pretty` or `json`; `--log-format` takes precedence.
+    pub format: Option<LogFormat>,
+    /// Filter directives such as `info,network=debug`; `--log-level` takes
+    /// precedence, and `RUST_LOG` over both.
+    pub level: Option<String>,
+}
+
+impl NodeSettings {
+    pub fn from_file(path: &Path) -> Result<Self, NodeError> {
+        let contents = std::fs::read_to_string(path).map_err(NodeError::config)?;
+        toml::from_str(&contents).map_err(NodeError::config)
+    }
+
+    /// The role to run as: the command-line choice if given, then the config
+    /// file's, then `full`.
+    pub fn role(&self, cli_role: Option<NodeRole>) -> NodeRole {
+        cli_role.or(self.node.role).unwrap_or_default()
+    }
+
+    /// The log format: the command-line choice if given, then the config
+    /// file's, then `pretty`.
+    pub fn log_format(&self, cli_format: Option<LogFormat>) -> LogFormat {
+        cli_format.or(self.logging.format).unwrap_or_default()
+    }
+
+    /// The configured log level, with `--log-level` winning over the config
+    /// file's. `None` leaves the choice to `logging::filter_directives`.
+    pub fn log_level<'a>(&'a self, cli_level: Option<&'a str>) -> Option<&'a str> {
+        cli_level.or(self.logging.level.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_role_read_from_config_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "[node]\nid = \"node-001\"\nrole = \"compute-only\"\n\n[consensus]\nblock_time = 5\n").unwrap();
+
+        let settings = NodeSettings::from_file(file.path()).unwrap();
+        assert_eq!(settings.node.role, Some(NodeRole::ComputeOnly));
+        assert_eq!(settings.role(None), NodeRole::ComputeOnly);
+        // The command line wins over the file
+        assert_eq!(settings.role(Some(NodeRole::Validator)), NodeRole::Validator);
+    }
+
+    #[test]
+    fn test_role_defaults_to_full() {
+        let settings: NodeSettings = toml::from_str("[logging]\nlevel = \"info\"\n").unwrap();
+        assert_eq!(settings.role(None), NodeRole::Full);
+        assert!(toml::from_str::<NodeSettings>("[node]\nrole = \"observer\"\n").is_err());
+    }
+
+    #[test]
+    fn test_log_format_read_from_config_file() {
+        let settings: NodeSettings = toml::from_str("[logging]\nformat = \"json\"\nlog_dir = \"./logs\"\n").unwrap();
+        assert_eq!(settings.log_format(None), LogFormat::Json);
+        assert_eq!(settings.log_format(Some(LogFormat::Pretty)), LogFormat::Pretty);
+        assert_eq!(NodeSettings::default().log_format(None), LogFormat::Pretty);
+    }
+
+    #[test]
+    fn test_log_level_read_from_config_file() {
+        let settings: NodeSettings = toml::from_str("[logging]\nlevel = \"warn,network=debug\"\n").unwrap();
+        assert_eq!(settings.log_level(None), Some("warn,network=debug"));
+        assert_eq!(settings.log_level(Some("trace")), Some("trace"));
+        assert_eq!(NodeSettings::default().log_level(None), None);
+
+        assert_eq!(
+            crate::logging::filter_directives(None, settings.log_level(None)),
+            "warn,network=debug,omnitensor_node::network=debug"
+        );
+    }
+}
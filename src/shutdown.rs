@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Tasks this node accepted and hasn't reported back on yet, and whether it
+/// still takes new ones.
+#[derive(Debug)]
+pub struct InFlightTasks {
+    tasks: Mutex<HashSet<String>>,
+    accepting: Mutex<bool>,
+}
+
+impl InFlightTasks {
+    pub fn new() -> Self {
+        Self {
+            tasks: Mutex::new(HashSet::new()),
+            accepting: Mutex::new(true),
+        }
+    }
+
+    pub fn started(&self, task_id: &str) {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner()).insert(task_id.to_string());
+    }
+
+    pub fn finished(&self, task_id: &str) {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner()).remove(task_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether new tasks may still be accepted.
+    pub fn accepting(&self) -> bool {
+        *self.accepting.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stops taking new tasks; the ones already accepted keep running.
+    pub fn stop_intake(&self) {
+        *self.accepting.lock().unwrap_or_else(|e| e.into_inner()) = false;
+    }
+}
+
+impl Default for InFlightTasks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stops intake and keeps handling events from `next_event` until every
+/// in-flight task has reported back, so completions that arrive while
+/// draining still get recorded and broadcast. `handle` is expected to call
+/// [`InFlightTasks::finished`] for tasks that complete or fail.
+///
+/// Returns false if tasks were still running after `timeout`, or if the
+/// event source ended first.
+pub async fn drain<E, N, NF, H, HF>(in_flight: &InFlightTasks, timeout: Duration, mut next_event: N, mut handle: H) -> bool
+where
+    N: FnMut() -> NF,
+    NF: Future<Output = Option<E>>,
+    H: FnMut(E) -> HF,
+    HF: Future<Output = ()>,
+{
+    in_flight.stop_intake();
+    let drained = async {
+        while !in_flight.is_empty() {
+            match next_event().await {
+                Some(event) => handle(event).await,
+                None => return false,
+            }
+        }
+        true
+    };
+    tokio::time::timeout(timeout, drained).await.unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    enum Event {
+        Completed(&'static str),
+        Received(&'static str),
+    }
+
+    #[tokio::test]
+    async fn test_drain_handles_completions_until_idle() {
+        tokio::time::pause();
+        let in_flight = InFlightTasks::new();
+        in_flight.started("a");
+        in_flight.started("b");
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let rx = &tokio::sync::Mutex::new(rx);
+        tokio::spawn(async move {
+            tx.send(Event::Completed("a")).ok();
+            tx.send(Event::Received("c")).ok();
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            tx.send(Event::Completed("b")).ok();
+            // Stay open so the drain can only end by going idle
+            std::future::pending::<()>().await;
+        });
+
+        let handled = Mutex::new(Vec::new());
+        let drained = drain(&in_flight, Duration::from_secs(5), || async move { rx.lock().await.recv().await }, |event| {
+            match event {
+                Event::Completed(id) => {
+                    in_flight.finished(id);
+                    handled.lock().unwrap().push(id);
+                },
+                Event::Received(id) => {
+                    // Intake is closed, so new tasks are turned away
+                    if in_flight.accepting() {
+                        in_flight.started(id);
+                    }
+                },
+            }
+            async {}
+        })
+        .await;
+
+        assert!(drained);
+        assert!(in_flight.is_empty());
+        assert!(!in_flight.accepting());
+        assert_eq!(*handled.lock().unwrap(), vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_drain_times_out_with_tasks_running() {
+        tokio::time::pause();
+        let in_flight = InFlightTasks::new();
+        in_flight.started("stuck");
+
+        let (_tx, rx) = mpsc::unbounded_channel::<Event>();
+        let rx = &tokio::sync::Mutex::new(rx);
+        let drained = drain(&in_flight, Duration::from_secs(5), || async move { rx.lock().await.recv().await }, |_| async {}).await;
+
+        assert!(!drained);
+        assert_eq!(in_flight.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_without_running_tasks_returns_at_once() {
+        let in_flight = InFlightTasks::new();
+        let (_tx, rx) = mpsc::unbounded_channel::<Event>();
+        let rx = &tokio::sync::Mutex::new(rx);
+        assert!(drain(&in_flight, Duration::from_secs(5), || async move { rx.lock().await.recv().await }, |_| async {}).await);
+        assert!(!in_flight.accepting());
+    }
+}
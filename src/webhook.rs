@@ -0,0 +1,371 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::identity::NodeIdentity;
+
+/// Header carrying the id of the node that sent a notification.
+pub const NODE_ID_HEADER: &str = "X-OmniTensor-Node-Id";
+/// Header carrying the hex-encoded ed25519 signature of `signed_message`.
+pub const SIGNATURE_HEADER: &str = "X-OmniTensor-Signature";
+/// Header carrying the Unix time in seconds at which the request was signed.
+pub const TIMESTAMP_HEADER: &str = "X-OmniTensor-Timestamp";
+
+/// What the signature covers: `<timestamp>.<body>`. Signing the timestamp lets
+/// receivers reject old requests replayed at them.
+pub fn signed_message(timestamp: &str, body: &[u8]) -> Vec<u8> {
+    [timestamp.as_bytes(), b".", body].concat()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskOutcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Body POSTed to a task's `callback_url` once it finishes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskNotification {
+    pub task_id: String,
+    pub status: TaskOutcome,
+    /// SHA-256 of the task output, hex-encoded; set for completed tasks.
+    pub result_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TaskNotification {
+    pub fn completed(task_id: &str, result_hash: String) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            status: TaskOutcome::Completed,
+            result_hash: Some(result_hash),
+            error: None,
+        }
+    }
+
+    pub fn failed(task_id: &str, error: impl ToString) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            status: TaskOutcome::Failed,
+            result_hash: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn cancelled(task_id: &str) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            status: TaskOutcome::Cancelled,
+            result_hash: None,
+            error: None,
+        }
+    }
+}
+
+/// Delivers signed task notifications to integrator webhooks, retrying with
+/// exponential backoff. Delivery never affects task accounting; failures are
+/// only logged.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    identity: Arc<NodeIdentity>,
+    max_attempts: u32,
+    base_backoff: Duration,
+    allow_internal_hosts: bool,
+}
+
+impl WebhookNotifier {
+    pub fn new(identity: Arc<NodeIdentity>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                // A redirect could lead to a host the callback check would refuse
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+            identity,
+            max_attempts: 5,
+            base_backoff: Duration::from_secs(1),
+            allow_internal_hosts: false,
+        }
+    }
+
+    /// Lets callbacks reach loopback, link-local and private addresses, e.g.
+    /// when integrators run on the node's own network. Off by default.
+    pub fn with_internal_hosts_allowed(mut self, allowed: bool) -> Self {
+        self.allow_internal_hosts = allowed;
+        self
+    }
+
+    /// Refuses URLs that aren't http(s), and ones pointing at this machine or
+    /// a non-public network, which a task submitter could otherwise use to
+    /// make the node probe internal services.
+    fn check_url(&self, url: &str) -> Result<(), &'static str> {
+        let parsed = reqwest::Url::parse(url).map_err(|_| "not a valid URL")?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("not an http(s) URL");
+        }
+        if !self.allow_internal_hosts && parsed.host_str().is_none_or(is_internal_host) {
+            return Err("points at a loopback, link-local or private host");
+        }
+        Ok(())
+    }
+
+    /// Sets how many times a notification is sent before giving up, and the
+    /// delay before the first retry, which doubles on each further attempt.
+    pub fn with_retries(mut self, max_attempts: u32, base_backoff: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_backoff = base_backoff;
+        self
+    }
+
+    /// Delivers `notification` in the background.
+    pub fn notify(self: &Arc<Self>, url: String, notification: TaskNotification) {
+        let notifier = Arc::clone(self);
+        tokio::spawn(async move {
+            notifier.deliver(&url, &notification).await;
+        });
+    }
+
+    /// POSTs `notification` to `url` until it's accepted with a 2xx status or
+    /// the attempts run out. Returns whether it was delivered; URLs refused by
+    /// the callback check are never contacted.
+    pub async fn deliver(&self, url: &str, notification: &TaskNotification) -> bool {
+        if let Err(reason) = self.check_url(url) {
+            warn!("Not notifying {} about task {}: callback URL {}", url, notification.task_id, reason);
+            return false;
+        }
+        let body = match serde_json::to_vec(notification) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize notification for task {}: {}", notification.task_id, e);
+                return false;
+            }
+        };
+
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(self.base_backoff.saturating_mul(2u32.saturating_pow(attempt - 1))).await;
+            }
+            // Signed afresh on each attempt, so retries aren't rejected as stale
+            let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+            let signature = hex::encode(self.identity.sign(&signed_message(&timestamp, &body)).to_bytes());
+            let response = self.client.post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(NODE_ID_HEADER, self.identity.node_id())
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send().await
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Delivered notification for task {} to {}", notification.task_id, url);
+                    return true;
+                },
+                // Redirects aren't followed, so they count as failures
+                Ok(response) => debug!("Notification for task {} to {} answered {} (attempt {})", notification.task_id, url, response.status(), attempt + 1),
+                Err(e) => debug!("Notification for task {} to {} failed (attempt {}): {}", notification.task_id, url, attempt + 1, e),
+            }
+        }
+
+        warn!("Giving up on notification for task {} to {} after {} attempt(s)", notification.task_id, url, self.max_attempts);
+        false
+    }
+}
+
+/// Whether a callback host names this machine or a non-public network.
+pub(crate) fn is_internal_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => is_internal_ipv4(ip),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(is_internal_ipv4)
+        },
+        Err(_) => {
+            let name = host.trim_end_matches('.').to_ascii_lowercase();
+            name == "localhost" || name.ends_with(".localhost")
+        },
+    }
+}
+
+fn is_internal_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || first == 0
+        // Carrier-grade NAT (100.64.0.0/10)
+        || (first == 100 && (second & 0xc0) == 64)
+}
+
+/// Minimal HTTP server recording the headers and body of each POST it receives.
+#[cfg(test)]
+pub(crate) mod test_server {
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::mpsc;
+
+    pub struct ReceivedPost {
+        pub headers: HashMap<String, String>,
+        pub body: Vec<u8>,
+    }
+
+    /// Answers requests with `statuses` in order, then with 200. Returns the
+    /// URL to post to and the stream of received requests.
+    pub async fn capture_posts(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<ReceivedPost>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let mut reader = BufReader::new(reader);
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).await.unwrap();
+                    match line.trim_end().split_once(": ") {
+                        Some((name, value)) => headers.insert(name.to_lowercase(), value.to_string()),
+                        None => break,
+                    };
+                }
+                let length = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+
+                let status = statuses.next().unwrap_or(200);
+                sender.send(ReceivedPost { headers, body }).ok();
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                writer.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (format!("http://{}/hook", addr), receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_server::capture_posts;
+    use ed25519_dalek::Signature;
+    use std::convert::TryFrom;
+
+    fn identity(dir: &tempfile::TempDir) -> Arc<NodeIdentity> {
+        Arc::new(NodeIdentity::load_or_generate(dir.path()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_notification_is_signed() {
+        let dir = tempfile::tempdir().unwrap();
+        let notifier = WebhookNotifier::new(identity(&dir)).with_internal_hosts_allowed(true);
+        let (url, mut posts) = capture_posts(vec![]).await;
+
+        let notification = TaskNotification::completed("task1", "ab".repeat(32));
+        assert!(notifier.deliver(&url, &notification).await);
+
+        let post = posts.recv().await.unwrap();
+        assert_eq!(serde_json::from_slice::<TaskNotification>(&post.body).unwrap(), notification);
+        let node_id = &post.headers[&NODE_ID_HEADER.to_lowercase()];
+        let timestamp = &post.headers[&TIMESTAMP_HEADER.to_lowercase()];
+        let signature = hex::decode(&post.headers[&SIGNATURE_HEADER.to_lowercase()]).unwrap();
+        let signature = Signature::try_from(signature.as_slice()).unwrap();
+        assert!(crate::identity::verify(node_id, &signed_message(timestamp, &post.body), &signature).is_ok());
+
+        // The signature is bound to the timestamp it was sent with
+        let sent_at: u64 = timestamp.parse().unwrap();
+        assert!(crate::identity::verify(node_id, &post.body, &signature).is_err());
+        assert!(crate::identity::verify(node_id, &signed_message(&(sent_at + 60).to_string(), &post.body), &signature).is_err());
+    }
+
+    #[test]
+    fn test_cancelled_notification_body() {
+        let body = serde_json::to_value(TaskNotification::cancelled("task1")).unwrap();
+        assert_eq!(body, serde_json::json!({
+            "task_id": "task1",
+            "status": "cancelled",
+            "result_hash": null,
+            "error": null,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_delivery_retries_then_gives_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let notifier = WebhookNotifier::new(identity(&dir)).with_internal_hosts_allowed(true).with_retries(3, Duration::from_millis(10));
+
+        let (url, mut posts) = capture_posts(vec![503, 500]).await;
+        assert!(notifier.deliver(&url, &TaskNotification::failed("task1", "out of memory")).await);
+        for _ in 0..3 {
+            posts.recv().await.unwrap();
+        }
+
+        let (url, mut posts) = capture_posts(vec![500, 500, 500]).await;
+        assert!(!notifier.deliver(&url, &TaskNotification::failed("task2", "out of memory")).await);
+        for _ in 0..3 {
+            posts.recv().await.unwrap();
+        }
+        assert!(posts.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_internal_callback_hosts_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let notifier = WebhookNotifier::new(identity(&dir));
+
+        // The test server is local, so nothing is sent to it
+        let (url, mut posts) = capture_posts(vec![]).await;
+        assert!(!notifier.deliver(&url, &TaskNotification::cancelled("task1")).await);
+        assert!(posts.try_recv().is_err());
+
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.0.0.5/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "ftp://hooks.example.com/task",
+            "not a url",
+        ] {
+            assert!(notifier.check_url(url).is_err(), "{} was accepted", url);
+        }
+        for url in ["https://hooks.example.com/task", "http://203.0.113.7:8080/hook", "http://[2001:db8::1]/hook"] {
+            assert!(notifier.check_url(url).is_ok(), "{} was refused", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirects_not_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let notifier = WebhookNotifier::new(identity(&dir)).with_internal_hosts_allowed(true).with_retries(1, Duration::ZERO);
+
+        let (url, mut posts) = capture_posts(vec![302]).await;
+        assert!(!notifier.deliver(&url, &TaskNotification::cancelled("task1")).await);
+        posts.recv().await.unwrap();
+        assert!(posts.try_recv().is_err());
+    }
+}